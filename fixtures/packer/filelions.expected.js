@@ -0,0 +1 @@
+var links={"hls2":"https://lions.cdn.example/hls2/05/02041/q9w8e7r6t5y4_,l,n,.urlset/master.m3u8?t=Abc&s=1700000000&e=10800&f=10205000&srv=25&asn=0&sp=4000","hls4":"/stream/Q9w8E7/master.m3u8"};jwplayer("vplayer").setup({sources:[{file:links.hls4||links.hls2,type:"hls"}],image:"https://lions.cdn.example/q9w8e7r6t5y4_xt.jpg",width:"100%",height:"100%",stretching:"uniform",duration:"1420.71",preload:'metadata',androidhls:"true",tracks:[],captions:{color:'#FFFFFF',fontSize:16,backgroundOpacity:0},"advertising":{}});
@@ -0,0 +1 @@
+var player=jwplayer("vplayer");player.setup({sources:[{file:"https://s1.wishcdn.example/hls2/01/00123/abcdef_,l,n,h,.urlset/master.m3u8?t=Xyz123&s=1700000000&e=129600"}],image:"https://s1.wishcdn.example/thumbs/abcdef.jpg",width:"100%",height:"100%",stretching:"uniform",duration:"1440.04",tracks:[{file:"https://s1.wishcdn.example/subs/abcdef_eng.vtt",label:"English",kind:"captions"},{file:"/dl?op=get_slides&length=1440&url=https://s1.wishcdn.example/thumbs/abcdef0000.jpg",kind:"thumbnails"}],captions:{userFontScale:1,color:'#FFFFFF',backgroundColor:'#303030',fontFamily:"Tahoma",backgroundOpacity:50,fontOpacity:'100'},'qualityLabels':{"1700":"1080p","800":"720p"},abouttext:"StreamWish",aboutlink:"https://streamwish.example",skin:{controlbar:{text:"#FFFFFF"}},playbackRateControls:true,playbackRates:[0.5,0.75,1,1.25,1.5,2],cast:{}});
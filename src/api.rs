@@ -1,28 +1,27 @@
 use anyhow::{Result, anyhow};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::models::{pair_variants, AnimeItem, EpisodeItem, ServerKind, StreamServer, StreamSource, SubtitleTrack};
+use crate::provider::AnimeProvider;
 
 // Update the API endpoint to the working version
 const BASE_URL: &str = "https://consumet-api-seven.vercel.app";
 
-#[derive(Debug, Deserialize)]
-pub struct AnimeItem {
-    pub id: String,
-    pub title: String,
-}
+// Servidores aceitos pelo parâmetro `server` do endpoint de streaming
+const SERVERS: [(ServerKind, &str); 3] = [
+    (ServerKind::Gogo, "gogocdn"),
+    (ServerKind::Vidstreaming, "vidstreaming"),
+    (ServerKind::Streamwish, "streamwish"),
+];
 
 #[derive(Debug, Deserialize)]
 struct SearchResponse {
     results: Vec<AnimeItem>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct EpisodeItem {
-    pub id: String,
-    pub number: String,
-    pub title: Option<String>,
-}
-
 #[derive(Debug, Deserialize)]
 struct InfoResponse {
     episodes: Vec<EpisodeItem>,
@@ -30,7 +29,11 @@ struct InfoResponse {
 
 #[derive(Debug, Deserialize)]
 struct WatchResponse {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
     sources: Vec<Source>,
+    #[serde(default)]
+    subtitles: Vec<Subtitle>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +42,52 @@ struct Source {
     pub quality: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct Subtitle {
+    pub url: String,
+    pub lang: Option<String>,
+}
+
+/// Provedor baseado na Consumet Meta API (Gogoanime)
+pub struct ConsumetProvider;
+
+#[async_trait]
+impl AnimeProvider for ConsumetProvider {
+    fn name(&self) -> &'static str {
+        "consumet"
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<AnimeItem>> {
+        search_anime(query).await
+    }
+
+    async fn episodes(&self, anime_id: &str) -> Result<Vec<EpisodeItem>> {
+        get_episodes(anime_id).await
+    }
+
+    async fn servers(&self, episode_id: &str) -> Result<Vec<StreamServer>> {
+        // A API resolve os servidores por conta própria, basta informar qual usar
+        Ok(SERVERS
+            .iter()
+            .map(|(kind, name)| StreamServer {
+                kind: *kind,
+                name: name.to_string(),
+                url: episode_id.to_string(),
+                referer: None,
+            })
+            .collect())
+    }
+
+    async fn resolve(&self, server: &StreamServer) -> Result<Vec<StreamSource>> {
+        let name = SERVERS
+            .iter()
+            .find(|(kind, _)| *kind == server.kind)
+            .map(|(_, name)| *name)
+            .unwrap_or("gogocdn");
+        get_sources(&server.url, name).await
+    }
+}
+
 /// Busca animes via Consumet Meta API (Gogoanime)
 pub async fn search_anime(query: &str) -> Result<Vec<AnimeItem>> {
     let url = format!("{}/anime/gogoanime/{}", BASE_URL, query);
@@ -62,7 +111,7 @@ pub async fn search_anime(query: &str) -> Result<Vec<AnimeItem>> {
     if search.results.is_empty() {
         anyhow::bail!("Nenhum anime encontrado para: \"{}\"", query);
     }
-    Ok(search.results)
+    Ok(pair_variants(search.results))
 }
 
 /// Obtém lista de episódios para um anime específico
@@ -91,9 +140,9 @@ pub async fn get_episodes(anime_id: &str) -> Result<Vec<EpisodeItem>> {
     Ok(info.episodes)
 }
 
-/// Obtém o melhor link de streaming para um episódio específico
-pub async fn get_stream_url(episode_id: &str) -> Result<String> {
-    let url = format!("{}/anime/gogoanime/watch/{}", BASE_URL, episode_id);
+/// Obtém os links de streaming de um episódio, do melhor para o pior
+pub async fn get_sources(episode_id: &str, server: &str) -> Result<Vec<StreamSource>> {
+    let url = format!("{}/anime/gogoanime/watch/{}?server={}", BASE_URL, episode_id, server);
     println!("Buscando stream em: {}", url);
     
     let resp = Client::new()
@@ -109,12 +158,28 @@ pub async fn get_stream_url(episode_id: &str) -> Result<String> {
 
     let body = resp.text().await?;
     
-    let watch: WatchResponse = serde_json::from_str(&body)?;
-    let best = watch.sources
-        .into_iter()
-        .max_by_key(|s| s.quality.parse::<u32>().unwrap_or(0))
-        .ok_or_else(|| anyhow!("No sources available"))?;
+    let mut watch: WatchResponse = serde_json::from_str(&body)?;
+    if watch.sources.is_empty() {
+        return Err(anyhow!("No sources available"));
+    }
+
+    // Ordena pela qualidade numérica, da maior para a menor
+    watch.sources.sort_by_key(|s| std::cmp::Reverse(s.quality.trim_end_matches('p').parse::<u32>().unwrap_or(0)));
     
-    println!("URL de stream encontrada: {}", &best.url);
-    Ok(best.url)
-}
\ No newline at end of file
+    println!("URL de stream encontrada: {}", &watch.sources[0].url);
+    // A Consumet lista miniaturas do vídeo junto das legendas
+    let subtitles: Vec<SubtitleTrack> = watch.subtitles
+        .into_iter()
+        .filter(|s| !s.lang.as_deref().is_some_and(|l| l.eq_ignore_ascii_case("thumbnails")))
+        .map(|s| SubtitleTrack { url: s.url, label: s.lang })
+        .collect();
+    Ok(watch.sources
+        .into_iter()
+        .map(|s| StreamSource {
+            url: s.url,
+            quality: Some(s.quality),
+            headers: watch.headers.clone(),
+            subtitles: subtitles.clone(),
+        })
+        .collect())
+}
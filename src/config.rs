@@ -0,0 +1,46 @@
+use anyhow::{Result, Context};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::player::PlayerKind;
+
+/// Configuração do usuário, lida de `config.json` no diretório de
+/// configuração. Todos os campos são opcionais e as opções da linha de
+/// comando têm prioridade sobre eles
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub player: Option<PlayerKind>,     // Player padrão (mpv, vlc, ffplay, custom)
+    pub player_command: Option<String>, // Comando do player custom
+    pub player_path: Option<PathBuf>,   // Executável do player, sem procurar no sistema
+}
+
+impl Config {
+    // Carrega a configuração do arquivo, ou a padrão se ele não existir
+    pub fn load() -> Result<Self> {
+        let path = get_config_path()?;
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .context("Falha ao ler o arquivo de configuração")?;
+        if contents.trim().is_empty() {
+            return Ok(Config::default());
+        }
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Configuração inválida em {}", path.display()))
+    }
+}
+
+// Obtém o caminho para o arquivo de configuração
+pub fn get_config_path() -> Result<PathBuf> {
+    let mut path = dirs::config_dir()
+        .context("Não foi possível determinar o diretório de configuração")?;
+
+    path.push("ani-cli-rust");
+    path.push("config.json");
+
+    Ok(path)
+}
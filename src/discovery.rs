@@ -0,0 +1,273 @@
+use anyhow::{Result, anyhow};
+use std::env;
+use std::fs::DirBuilder;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Procura dos players instalados. Cada forma de instalação (PATH, pastas
+// comuns, Snap, Flatpak) é testada em ordem e o motivo de cada candidato
+// recusado é guardado para a mensagem de erro
+
+/// Como encontrar um player: nomes do executável e os ids nos gerenciadores
+/// de pacotes
+pub struct PlayerSpec {
+    pub name: &'static str,
+    pub binaries: &'static [&'static str],
+    pub snap: Option<&'static str>,    // Comando em /snap/bin
+    pub flatpak: Option<&'static str>, // Id do app no Flatpak
+}
+
+pub const MPV: PlayerSpec = PlayerSpec {
+    name: "mpv",
+    binaries: &["mpv"],
+    snap: Some("mpv"),
+    flatpak: Some("io.mpv.Mpv"),
+};
+
+pub const VLC: PlayerSpec = PlayerSpec {
+    name: "VLC",
+    binaries: &["vlc"],
+    snap: Some("vlc"),
+    flatpak: Some("org.videolan.VLC"),
+};
+
+pub const FFPLAY: PlayerSpec = PlayerSpec {
+    name: "ffplay",
+    binaries: &["ffplay"],
+    snap: Some("ffmpeg.ffplay"),
+    flatpak: None,
+};
+
+// Pastas onde os players costumam ser instalados fora do PATH (Homebrew,
+// MacPorts, instalações locais...)
+const COMMON_DIRS: [&str; 6] = [
+    "/usr/local/bin",
+    "/usr/bin",
+    "/bin",
+    "/opt/homebrew/bin",
+    "/opt/local/bin",
+    "/usr/games",
+];
+
+/// Comando que executa um player: o programa e os argumentos que vêm antes
+/// dos do player (ex: `flatpak` + `run io.mpv.Mpv`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerCommand {
+    pub program: PathBuf,
+    pub prefix_args: Vec<String>,
+    pub flatpak: Option<String>, // Id do app, quando roda pelo Flatpak
+}
+
+impl PlayerCommand {
+    fn new(program: PathBuf) -> Self {
+        Self { program, prefix_args: Vec::new(), flatpak: None }
+    }
+
+    /// Pasta para arquivos trocados com o player (socket IPC, capítulos).
+    /// Apps do Flatpak não enxergam o /tmp do sistema, só a própria pasta em
+    /// $XDG_RUNTIME_DIR/app, que é criada se ainda não existir
+    pub fn shared_dir(&self) -> Result<PathBuf> {
+        self.shared_dir_in(dirs::runtime_dir().as_deref())
+    }
+
+    fn shared_dir_in(&self, runtime_dir: Option<&Path>) -> Result<PathBuf> {
+        let Some(app) = &self.flatpak else {
+            return Ok(env::temp_dir());
+        };
+        let runtime_dir = runtime_dir
+            .ok_or_else(|| anyhow!("XDG_RUNTIME_DIR não definido; o {} do Flatpak não teria onde criar o socket", app))?;
+
+        let dir = runtime_dir.join("app").join(app);
+        let mut builder = DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder
+            .create(&dir)
+            .map_err(|e| anyhow!("Falha ao criar {}: {}", dir.display(), e))?;
+        Ok(dir)
+    }
+
+    /// Cria o processo com o programa e os argumentos iniciais
+    pub fn command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(&self.prefix_args);
+        command
+    }
+}
+
+// Candidato recusado e o motivo
+struct Rejection {
+    candidate: String,
+    reason: String,
+}
+
+/// Encontra o player. Um caminho explícito (da configuração) é usado sem
+/// procurar em outros lugares; sem ele, tenta o PATH, as pastas comuns, o
+/// Snap e o Flatpak. O erro lista cada candidato recusado e o motivo
+pub fn find_player(spec: &PlayerSpec, explicit: Option<&Path>) -> Result<PlayerCommand> {
+    let mut rejected = Vec::new();
+
+    if let Some(path) = explicit {
+        return match resolve_program(path) {
+            Ok(program) => Ok(PlayerCommand::new(program)),
+            Err(reason) => Err(anyhow!(
+                "{} não encontrado no caminho configurado {}: {}",
+                spec.name,
+                path.display(),
+                reason
+            )),
+        };
+    }
+
+    let path_dirs = path_dirs();
+    for binary in spec.binaries {
+        match search_path(binary) {
+            Ok(program) => return Ok(PlayerCommand::new(program)),
+            Err(reason) => rejected.push(Rejection { candidate: format!("{} no PATH", binary), reason }),
+        }
+        // Pastas que já estão no PATH foram verificadas acima
+        for dir in COMMON_DIRS.iter().filter(|d| !path_dirs.iter().any(|p| p == Path::new(d))) {
+            let path = Path::new(dir).join(binary);
+            match check_executable(&path) {
+                Ok(()) => return Ok(PlayerCommand::new(path)),
+                Err(reason) => rejected.push(Rejection { candidate: path.display().to_string(), reason }),
+            }
+        }
+    }
+
+    if let Some(snap) = spec.snap {
+        let path = Path::new("/snap/bin").join(snap);
+        match check_executable(&path) {
+            Ok(()) => return Ok(PlayerCommand::new(path)),
+            Err(reason) => rejected.push(Rejection { candidate: format!("Snap ({})", path.display()), reason }),
+        }
+    }
+
+    if let Some(app) = spec.flatpak {
+        match check_flatpak(app) {
+            Ok(program) => {
+                return Ok(PlayerCommand {
+                    program,
+                    prefix_args: vec!["run".to_string(), app.to_string()],
+                    flatpak: Some(app.to_string()),
+                });
+            }
+            Err(reason) => rejected.push(Rejection { candidate: format!("Flatpak ({})", app), reason }),
+        }
+    }
+
+    let details: Vec<String> = rejected
+        .iter()
+        .map(|r| format!("  {}: {}", r.candidate, r.reason))
+        .collect();
+    Err(anyhow!("{} não encontrado. Locais verificados:\n{}", spec.name, details.join("\n")))
+}
+
+/// Resolve um programa informado pelo usuário: caminhos (com `/`) precisam
+/// existir; nomes soltos são procurados no PATH
+pub fn resolve_program(program: &Path) -> Result<PathBuf, String> {
+    if program.components().count() > 1 || program.is_absolute() {
+        check_executable(program)?;
+        return Ok(program.to_path_buf());
+    }
+    search_path(&program.to_string_lossy())
+}
+
+// Pastas do PATH, na ordem
+fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).filter(|d| !d.as_os_str().is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// Procura o executável nas pastas do PATH
+fn search_path(binary: &str) -> Result<PathBuf, String> {
+    let dirs = path_dirs();
+    if dirs.is_empty() {
+        return Err("PATH vazio".to_string());
+    }
+
+    let mut reason = "não está em nenhuma pasta do PATH".to_string();
+    for dir in dirs {
+        let candidate = dir.join(binary);
+        match check_executable(&candidate) {
+            Ok(()) => return Ok(candidate),
+            // Existe mas não pode ser executado: mais útil que "não está"
+            Err(e) if candidate.exists() => reason = format!("{}: {}", candidate.display(), e),
+            Err(_) => {}
+        }
+    }
+    Err(reason)
+}
+
+// Confere se o caminho é um arquivo executável
+fn check_executable(path: &Path) -> Result<(), String> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err("não existe".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+    if !metadata.is_file() {
+        return Err("não é um arquivo".to_string());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err("sem permissão de execução".to_string());
+        }
+    }
+    Ok(())
+}
+
+// Confere se o app está instalado no Flatpak; retorna o caminho do `flatpak`
+fn check_flatpak(app: &str) -> Result<PathBuf, String> {
+    let flatpak = search_path("flatpak").map_err(|_| "Flatpak não está instalado".to_string())?;
+    let status = Command::new(&flatpak)
+        .args(["info", app])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("falha ao executar flatpak: {}", e))?;
+    if !status.success() {
+        return Err("app não instalado".to_string());
+    }
+    Ok(flatpak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_dir_for_flatpak_is_created_in_runtime_dir() {
+        let runtime = env::temp_dir().join(format!("anirust-runtime-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&runtime);
+
+        let flatpak = PlayerCommand {
+            program: PathBuf::from("/usr/bin/flatpak"),
+            prefix_args: vec!["run".to_string(), "io.mpv.Mpv".to_string()],
+            flatpak: Some("io.mpv.Mpv".to_string()),
+        };
+        let dir = flatpak.shared_dir_in(Some(&runtime)).unwrap();
+        assert_eq!(dir, runtime.join("app/io.mpv.Mpv"));
+        assert!(dir.is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        // Já existindo, a mesma pasta é usada
+        assert_eq!(flatpak.shared_dir_in(Some(&runtime)).unwrap(), dir);
+        assert!(flatpak.shared_dir_in(None).is_err());
+
+        let native = PlayerCommand::new(PathBuf::from("/usr/bin/mpv"));
+        assert_eq!(native.shared_dir_in(Some(&runtime)).unwrap(), env::temp_dir());
+        let _ = std::fs::remove_dir_all(&runtime);
+    }
+}
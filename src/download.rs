@@ -0,0 +1,682 @@
+use aes::Aes128;
+use anyhow::{Context, Result, anyhow};
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use futures::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::{Client, StatusCode, header};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use crate::extractor::USER_AGENT;
+use crate::filename::sanitize_component;
+use crate::hls::{self, Segment};
+use crate::models::{Quality, StreamSource};
+use crate::utils::is_program_installed;
+
+type Aes128CbcDec = cbc::Decryptor<Aes128>;
+
+/// Tentativas extras após uma falha de rede ou download incompleto
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Segmentos HLS baixados ao mesmo tempo
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Opções de download
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub retries: u32,       // Novas tentativas por arquivo ou segmento
+    pub concurrency: usize, // Segmentos HLS baixados ao mesmo tempo
+    pub remux: bool,        // Converte o .ts de uma playlist HLS para .mp4 (ffmpeg)
+    pub progress: MultiProgress, // Onde as barras de progresso são desenhadas
+}
+
+impl DownloadOptions {
+    /// Mostra uma mensagem acima das barras de progresso (ou no terminal,
+    /// quando as barras estão ocultas, como com a saída redirecionada)
+    pub fn log(&self, message: impl AsRef<str>) {
+        if self.progress.is_hidden() {
+            println!("{}", message.as_ref());
+        } else {
+            let _ = self.progress.println(message);
+        }
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            concurrency: DEFAULT_CONCURRENCY,
+            remux: true,
+            progress: MultiProgress::new(),
+        }
+    }
+}
+
+// Espera antes da primeira nova tentativa; dobra a cada falha
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// Falha de uma tentativa de download. Só as temporárias (rede, 5xx,
+// arquivo truncado) são tentadas de novo
+enum Failure {
+    Retry(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(e: reqwest::Error) -> Self {
+        Failure::Retry(e.into())
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(e: std::io::Error) -> Self {
+        Failure::Fatal(e.into())
+    }
+}
+
+/// Baixa o vídeo da fonte para `output`. Os dados vão para `output.part` e,
+/// se o download for interrompido, continuam de onde pararam (HTTP Range) na
+/// próxima tentativa ou execução. Retorna o tamanho final em bytes
+pub async fn download_file(source: &StreamSource, output: &Path, options: &DownloadOptions) -> Result<u64> {
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let partial = partial_path(output);
+    let client = Client::new();
+    let bar = options.progress.add(ProgressBar::new_spinner());
+    bar.set_message(
+        output
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    );
+
+    let result = with_retries(options, || try_download(&client, source, &partial, &bar)).await;
+
+    match result {
+        Ok(size) => {
+            tokio::fs::rename(&partial, output).await?;
+            bar.finish_and_clear();
+            options.progress.remove(&bar);
+            Ok(size)
+        }
+        Err(e) => {
+            // O arquivo parcial fica no disco para continuar depois
+            bar.abandon();
+            Err(e)
+        }
+    }
+}
+
+// Repete a tentativa enquanto a falha for temporária, esperando cada vez mais
+async fn with_retries<T, F, Fut>(options: &DownloadOptions, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    let mut failures = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(Failure::Fatal(e)) => return Err(e),
+            Err(Failure::Retry(e)) if failures < options.retries => {
+                let delay = RETRY_DELAY * 2u32.pow(failures);
+                failures += 1;
+                options.log(format!(
+                    "Falha no download ({}), tentando de novo em {}s ({}/{})",
+                    e,
+                    delay.as_secs(),
+                    failures,
+                    options.retries
+                ));
+                tokio::time::sleep(delay).await;
+            }
+            Err(Failure::Retry(e)) => {
+                return Err(e.context(format!("Download falhou após {} tentativas", failures + 1)));
+            }
+        }
+    }
+}
+
+/// Apaga os dados parciais de um download (`.part` e `.ts.parts/`), para que
+/// ele recomece do zero
+pub async fn discard_partial(output: &Path) {
+    let _ = tokio::fs::remove_file(partial_path(output)).await;
+    let _ = tokio::fs::remove_dir_all(hls_parts_dir(&output.with_extension("ts"))).await;
+}
+
+// Arquivo onde os dados ficam até o download terminar ("video.mp4.part")
+fn partial_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+// Uma tentativa: pede os bytes que faltam e os acrescenta ao arquivo parcial
+async fn try_download(client: &Client, source: &StreamSource, partial: &Path, bar: &ProgressBar) -> Result<u64, Failure> {
+    let offset = match tokio::fs::metadata(partial).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    let mut request = get(client, &source.url, &source.headers);
+    if offset > 0 {
+        request = request.header(header::RANGE, format!("bytes={}-", offset));
+    }
+
+    let mut resp = request.send().await?;
+    let status = resp.status();
+
+    // O arquivo parcial já tem todos os bytes: o servidor responde 416 e
+    // informa o tamanho total em "Content-Range: bytes */1234"
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+        if content_range_total(&resp) == Some(offset) {
+            return Ok(offset);
+        }
+        tokio::fs::remove_file(partial).await?;
+        return Err(Failure::Retry(anyhow!("Arquivo parcial inválido, recomeçando do zero")));
+    }
+
+    if !status.is_success() {
+        let error = anyhow!("HTTP {}", status);
+        let temporary = status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS;
+        return Err(if temporary { Failure::Retry(error) } else { Failure::Fatal(error) });
+    }
+
+    check_content_type(&resp).map_err(Failure::Fatal)?;
+
+    // 206 continua de onde parou; 200 significa que o servidor ignorou o
+    // Range e mandou o arquivo inteiro
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    let (mut file, mut written) = if resumed {
+        (OpenOptions::new().append(true).open(partial).await?, offset)
+    } else {
+        (File::create(partial).await?, 0)
+    };
+
+    let total = if resumed {
+        content_range_total(&resp)
+    } else {
+        resp.content_length()
+    };
+    match total {
+        Some(total) => {
+            bar.set_length(total);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            );
+        }
+        None => bar.set_style(ProgressStyle::with_template("{spinner} {msg} {bytes} ({bytes_per_sec})").unwrap()),
+    }
+    bar.set_position(written);
+
+    loop {
+        let chunk = match resp.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                // Guarda o que já chegou para a próxima tentativa continuar
+                file.flush().await?;
+                return Err(Failure::Retry(anyhow!("Conexão interrompida: {}", e)));
+            }
+        };
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        bar.set_position(written);
+    }
+    file.flush().await?;
+
+    if let Some(total) = total
+        && written != total
+    {
+        return Err(Failure::Retry(anyhow!("Download incompleto: {} de {} bytes", written, total)));
+    }
+    if written == 0 {
+        return Err(Failure::Fatal(anyhow!("O servidor não enviou nenhum dado")));
+    }
+    Ok(written)
+}
+
+// GET com o User-Agent padrão e os cabeçalhos exigidos pela fonte
+fn get(client: &Client, url: &str, headers: &BTreeMap<String, String>) -> reqwest::RequestBuilder {
+    let mut request = client.get(url).header(header::USER_AGENT, USER_AGENT);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    request
+}
+
+// Tamanho total informado em "Content-Range: bytes 100-999/1000"
+fn content_range_total(resp: &reqwest::Response) -> Option<u64> {
+    let range = resp.headers().get(header::CONTENT_RANGE)?.to_str().ok()?;
+    range.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+// Recusa respostas que não são vídeo: páginas de erro, bloqueio ou captcha
+// costumam vir como HTML com status 200
+fn check_content_type(resp: &reqwest::Response) -> Result<()> {
+    let Some(content_type) = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Ok(());
+    };
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+
+    if mime.contains("mpegurl") {
+        return Err(anyhow!("A fonte é uma playlist HLS (.m3u8), não um arquivo de vídeo"));
+    }
+    if mime.starts_with("text/") || mime == "application/json" || mime == "application/xml" {
+        return Err(anyhow!("O servidor respondeu {} em vez de um vídeo", mime));
+    }
+    Ok(())
+}
+
+/// Baixa uma playlist HLS: os segmentos são baixados em paralelo (até
+/// `concurrency` ao mesmo tempo), decifrados se houver `#EXT-X-KEY` AES-128 e
+/// juntados em um único `.ts`, convertido para `.mp4` com o ffmpeg quando
+/// `remux` estiver ativo e o ffmpeg instalado. Os segmentos prontos ficam em
+/// `<arquivo>.parts/` até o fim, então um download interrompido continua de
+/// onde parou. Retorna o caminho do arquivo final
+pub async fn download_hls(source: &StreamSource, output: &Path, options: &DownloadOptions) -> Result<PathBuf> {
+    let client = Client::new();
+    let segments = media_segments(source).await?;
+
+    let ts_path = output.with_extension("ts");
+    if let Some(parent) = ts_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let parts = hls_parts_dir(&ts_path);
+    tokio::fs::create_dir_all(&parts).await?;
+
+    // Os segmentos no disco só servem se vieram da mesma playlist: outra
+    // variante ou um corte diferente dos segmentos não se encaixa neles
+    let manifest = playlist_manifest(&segments);
+    let manifest_path = parts.join("playlist.txt");
+    if let Ok(saved) = tokio::fs::read_to_string(&manifest_path).await
+        && saved != manifest
+    {
+        options.log(format!("A playlist de {} mudou, baixando do início", ts_path.display()));
+        tokio::fs::remove_dir_all(&parts).await?;
+        tokio::fs::create_dir_all(&parts).await?;
+    }
+    tokio::fs::write(&manifest_path, &manifest).await?;
+
+    // Segmentos que já estão no disco de uma execução anterior
+    let pending: Vec<(usize, &Segment)> = segments
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !segment_path(&parts, *idx).exists())
+        .collect();
+
+    let bar = options.progress.add(ProgressBar::new(segments.len() as u64));
+    bar.set_style(
+        ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} segmentos ({eta})")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    bar.set_message(
+        ts_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    );
+    bar.set_position((segments.len() - pending.len()) as u64);
+
+    // Cada chave é baixada uma vez, antes dos segmentos
+    let mut keys: HashMap<String, Vec<u8>> = HashMap::new();
+    for (_, segment) in &pending {
+        if let Some(key) = &segment.key
+            && !keys.contains_key(&key.url)
+        {
+            let data = with_retries(options, || fetch_bytes(&client, &key.url, &source.headers)).await?;
+            if data.len() != 16 {
+                bar.abandon();
+                return Err(anyhow!("Chave AES-128 inválida ({} bytes): {}", data.len(), key.url));
+            }
+            keys.insert(key.url.clone(), data);
+        }
+    }
+
+    let mut downloads = stream::iter(pending)
+        .map(|(idx, segment)| {
+            let (client, keys, bar, parts) = (&client, &keys, &bar, &parts);
+            async move {
+                let data = with_retries(options, || fetch_bytes(client, &segment.url, &source.headers))
+                    .await
+                    .with_context(|| format!("Segmento {}", idx))?;
+                let data = match &segment.key {
+                    Some(key) => decrypt_segment(&data, &keys[&key.url], &key.iv_for(segment.sequence))
+                        .with_context(|| format!("Segmento {}", idx))?,
+                    None => data,
+                };
+
+                // Grava com outro nome e renomeia: um segmento pela metade
+                // nunca é confundido com um pronto
+                let path = segment_path(parts, idx);
+                let temp = path.with_extension("tmp");
+                tokio::fs::write(&temp, &data).await?;
+                tokio::fs::rename(&temp, &path).await?;
+                bar.inc(1);
+                anyhow::Ok(())
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    while let Some(result) = downloads.next().await {
+        if let Err(e) = result {
+            bar.abandon();
+            return Err(e);
+        }
+    }
+    drop(downloads);
+    bar.finish_and_clear();
+    options.progress.remove(&bar);
+
+    // Junta os segmentos na ordem da playlist
+    let mut file = File::create(&ts_path).await?;
+    for idx in 0..segments.len() {
+        let data = tokio::fs::read(segment_path(&parts, idx)).await?;
+        file.write_all(&data).await?;
+    }
+    file.flush().await?;
+    tokio::fs::remove_dir_all(&parts).await?;
+
+    if !options.remux {
+        return Ok(ts_path);
+    }
+    if !is_program_installed("ffmpeg") {
+        options.log("ffmpeg não encontrado, o vídeo fica em .ts");
+        return Ok(ts_path);
+    }
+    match remux_to_mp4(&ts_path, options).await {
+        Ok(mp4_path) => Ok(mp4_path),
+        Err(e) => {
+            options.log(format!("Não foi possível converter para .mp4 ({}), o vídeo fica em .ts", e));
+            Ok(ts_path)
+        }
+    }
+}
+
+// Baixa a playlist da fonte e retorna os segmentos. Se for uma playlist
+// master, usa a variante de maior qualidade
+async fn media_segments(source: &StreamSource) -> Result<Vec<Segment>> {
+    let mut url = Url::parse(&source.url)?;
+    let mut content = hls::fetch_playlist(url.as_str(), &source.headers).await?;
+
+    if hls::is_master(&content) {
+        let variants = hls::parse_master(&content, &url);
+        let variant = hls::select_variant(&variants, Quality::Best)
+            .ok_or_else(|| anyhow!("Playlist master sem variantes"))?;
+        url = Url::parse(&variant.url)?;
+        content = hls::fetch_playlist(url.as_str(), &source.headers).await?;
+    }
+
+    hls::parse_media(&content, &url)
+}
+
+// Baixa um segmento ou chave inteiro para a memória
+async fn fetch_bytes(client: &Client, url: &str, headers: &BTreeMap<String, String>) -> Result<Vec<u8>, Failure> {
+    let resp = get(client, url, headers).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let error = anyhow!("HTTP {}: {}", status, url);
+        let temporary = status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS;
+        return Err(if temporary { Failure::Retry(error) } else { Failure::Fatal(error) });
+    }
+    Ok(resp.bytes().await?.to_vec())
+}
+
+// Decifra um segmento AES-128-CBC (PKCS7)
+fn decrypt_segment(data: &[u8], key: &[u8], iv: &[u8; 16]) -> Result<Vec<u8>> {
+    Aes128CbcDec::new_from_slices(key, iv)
+        .map_err(|e| anyhow!("Chave AES inválida: {}", e))?
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| anyhow!("Falha ao decifrar o segmento (chave ou IV incorretos)"))
+}
+
+// Descreve a lista de segmentos pelo nome do arquivo e a duração de cada um,
+// sem os tokens das URLs, que mudam toda vez que a fonte é resolvida
+fn playlist_manifest(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            let path = segment.url.split(['?', '#']).next().unwrap_or_default();
+            let name = path.rsplit('/').next().unwrap_or(path);
+            format!("{} {:.3} {}\n", segment.sequence, segment.duration, name)
+        })
+        .collect()
+}
+
+// Pasta dos segmentos já baixados ("video.ts.parts")
+fn hls_parts_dir(ts_path: &Path) -> PathBuf {
+    partial_path(ts_path).with_extension("parts")
+}
+
+// Arquivo de um segmento já baixado ("video.ts.parts/00042.ts")
+fn segment_path(parts: &Path, idx: usize) -> PathBuf {
+    parts.join(format!("{:05}.ts", idx))
+}
+
+// Converte o .ts para .mp4 sem recodificar e apaga o .ts
+async fn remux_to_mp4(ts_path: &Path, options: &DownloadOptions) -> Result<PathBuf> {
+    let mp4_path = ts_path.with_extension("mp4");
+    options.log(format!("Convertendo {} para .mp4 com o ffmpeg...", ts_path.display()));
+    let status = tokio::process::Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(ts_path)
+        .args(["-c", "copy", "-bsf:a", "aac_adtstoasc"])
+        .arg(&mp4_path)
+        .status()
+        .await?;
+    if !status.success() {
+        let _ = tokio::fs::remove_file(&mp4_path).await;
+        return Err(anyhow!("ffmpeg terminou com {}", status));
+    }
+    tokio::fs::remove_file(ts_path).await?;
+    Ok(mp4_path)
+}
+
+/// Salva as legendas da fonte ao lado do vídeo baixado, no formato reconhecido
+/// pelos servidores de mídia ("Anime - S01E03.en.vtt"). Legendas que já
+/// existem são mantidas e falhas viram avisos, sem perder o vídeo. Retorna
+/// quantas legendas foram salvas
+pub async fn download_subtitles(source: &StreamSource, video: &Path, options: &DownloadOptions) -> usize {
+    let Some(stem) = video.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+        return 0;
+    };
+    let dir = video.parent().unwrap_or(Path::new("."));
+    let client = Client::new();
+
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let mut saved = 0;
+    for track in &source.subtitles {
+        // Idioma, ou o rótulo quando não é reconhecido ("Signs & Songs")
+        let tag = match (track.language(), &track.label) {
+            (Some(code), _) => code.to_string(),
+            (None, Some(label)) => sanitize_component(label, None),
+            (None, None) => "sub".to_string(),
+        };
+        let count = used.entry(tag.clone()).or_insert(0);
+        *count += 1;
+        let tag = if *count == 1 { tag } else { format!("{}.{}", tag, count) };
+        let path = dir.join(format!("{}.{}.{}", stem, tag, track.extension()));
+        if path.exists() {
+            continue;
+        }
+
+        let result = with_retries(options, || fetch_bytes(&client, &track.url, &source.headers)).await;
+        match result {
+            Ok(data) => match tokio::fs::write(&path, data).await {
+                Ok(()) => saved += 1,
+                Err(e) => options.log(format!("Falha ao salvar a legenda {}: {}", path.display(), e)),
+            },
+            Err(e) => options.log(format!("Falha ao baixar a legenda ({}): {:#}", tag, e)),
+        }
+    }
+    if saved > 0 {
+        options.log(format!("{} legenda(s) salva(s) ao lado de {}", saved, video.display()));
+    }
+    saved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressDrawTarget;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/hls")
+    }
+
+    // Servidor HTTP mínimo com os arquivos de `fixtures/hls` em /hls/.
+    // Retorna o endereço base e os caminhos pedidos
+    async fn serve_fixtures() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/hls/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    let Ok(Some(request)) = lines.next_line().await else {
+                        return;
+                    };
+                    // Descarta os cabeçalhos
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line.is_empty() {
+                            break;
+                        }
+                    }
+
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    log.lock().unwrap().push(path.clone());
+                    let file = path.strip_prefix("/hls/").map(|p| fixtures().join(p));
+                    let response = match file.and_then(|f| std::fs::read(f).ok()) {
+                        Some(body) => {
+                            let mut response = format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            )
+                            .into_bytes();
+                            response.extend(body);
+                            response
+                        }
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    let _ = writer.write_all(&response).await;
+                });
+            }
+        });
+
+        (base, requests)
+    }
+
+    fn options() -> DownloadOptions {
+        DownloadOptions {
+            retries: 0,
+            concurrency: 2,
+            remux: false,
+            progress: MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        }
+    }
+
+    fn source(url: String) -> StreamSource {
+        StreamSource { url, quality: None, headers: BTreeMap::new(), subtitles: Vec::new() }
+    }
+
+    // Pasta de saída própria do teste
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("anirust-hls-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn decrypts_hls_download() {
+        let (base, requests) = serve_fixtures().await;
+        let dir = output_dir("full");
+
+        let path = download_hls(&source(format!("{}master.m3u8", base)), &dir.join("ep.mp4"), &options())
+            .await
+            .unwrap();
+
+        assert_eq!(path, dir.join("ep.ts"));
+        assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(fixtures().join("expected.ts")).unwrap());
+        assert!(!dir.join("ep.ts.parts").exists());
+
+        // A chave relativa é resolvida a partir da playlist de mídia, e só é
+        // baixada uma vez
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.iter().filter(|p| *p == "/hls/keys/key.bin").count(), 1);
+        assert!(!requests.iter().any(|p| p.starts_with("/hls/360p/")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn resumes_from_downloaded_segments() {
+        let (base, requests) = serve_fixtures().await;
+        let dir = output_dir("resume");
+        let source = source(format!("{}720p/index.m3u8", base));
+
+        // O primeiro segmento (3 pacotes de 188 bytes) já foi baixado e
+        // decifrado, da mesma playlist
+        let expected = std::fs::read(fixtures().join("expected.ts")).unwrap();
+        let parts = dir.join("ep.ts.parts");
+        std::fs::create_dir_all(&parts).unwrap();
+        let manifest = playlist_manifest(&media_segments(&source).await.unwrap());
+        std::fs::write(parts.join("playlist.txt"), &manifest).unwrap();
+        std::fs::write(segment_path(&parts, 0), &expected[..3 * 188]).unwrap();
+
+        let path = download_hls(&source, &dir.join("ep.mp4"), &options()).await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        {
+            let requests = requests.lock().unwrap();
+            assert!(!requests.iter().any(|p| p == "/hls/720p/seg7.ts"), "{:?}", requests);
+            assert!(requests.iter().any(|p| p == "/hls/720p/seg8.ts"));
+        }
+
+        // Segmentos de outra playlist (outra variante, com outro corte) são
+        // descartados em vez de juntados aos novos
+        requests.lock().unwrap().clear();
+        std::fs::create_dir_all(&parts).unwrap();
+        std::fs::write(parts.join("playlist.txt"), "0 6.000 other0.ts\n1 6.000 other1.ts\n").unwrap();
+        std::fs::write(segment_path(&parts, 0), b"segmento de outra variante").unwrap();
+        std::fs::write(segment_path(&parts, 1), b"segmento de outra variante").unwrap();
+
+        let path = download_hls(&source, &dir.join("ep.mp4"), &options()).await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        assert!(requests.lock().unwrap().iter().any(|p| p == "/hls/720p/seg7.ts"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::HashMap;
+use url::Url;
+
+use super::{fetch_page, host_matches, jwplayer_sources, origin, Extractor};
+use crate::models::StreamSource;
+
+// Domínios do FileLions e do VidHide, que usa o mesmo player
+const DOMAINS: [&str; 6] = [
+    "filelions.com",
+    "filelions.to",
+    "alions.pro",
+    "vidhide.com",
+    "vidhidepro.com",
+    "vidhidevip.com",
+];
+
+/// Extrator do FileLions/VidHide: jwplayer com a lista de `sources`
+pub struct FileLions;
+
+#[async_trait]
+impl Extractor for FileLions {
+    fn name(&self) -> &'static str {
+        "FileLions"
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        host_matches(host, &DOMAINS) || host.contains("lions") || host.contains("vidhide")
+    }
+
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+        let html = fetch_page(embed_url.as_str(), referer).await?;
+
+        let mut sources = jwplayer_sources(&html);
+
+        // Versões recentes apontam `file` para um objeto de links:
+        //   var links={"hls4":"/stream/.../master.m3u8","hls2":"https://..."};
+        //   sources:[{file:links.hls4||links.hls2,type:"hls"}]
+        if sources.is_empty() {
+            sources = links_sources(&html, embed_url);
+        }
+
+        for source in &mut sources {
+            source.headers.insert("Referer".to_string(), origin(embed_url));
+        }
+        Ok(sources)
+    }
+}
+
+// Lê as URLs do objeto `links`, na ordem de preferência do próprio player
+pub(super) fn links_sources(html: &str, embed_url: &Url) -> Vec<StreamSource> {
+    let links_re = Regex::new(r"links\s*=\s*(\{[^}]*\})").unwrap();
+    let Some(links) = links_re
+        .captures(html)
+        .and_then(|caps| serde_json::from_str::<HashMap<String, String>>(&caps[1]).ok())
+    else {
+        return Vec::new();
+    };
+
+    ["hls4", "hls3", "hls2"]
+        .iter()
+        .filter_map(|key| links.get(*key))
+        .filter_map(|link| embed_url.join(link).ok())
+        .map(|url| StreamSource {
+            url: url.to_string(),
+            ..Default::default()
+        })
+        .collect()
+}
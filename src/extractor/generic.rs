@@ -0,0 +1,123 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use scraper::{Html, Selector};
+use url::Url;
+
+use super::{fetch_page, Extractor};
+use crate::models::StreamSource;
+
+/// Extrator genérico: procura qualquer link de vídeo no HTML do player.
+/// Usado quando nenhum extrator específico reconhece o host
+pub struct Generic;
+
+#[async_trait]
+impl Extractor for Generic {
+    fn name(&self) -> &'static str {
+        "genérico"
+    }
+
+    fn matches(&self, _host: &str) -> bool {
+        true
+    }
+
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+        let player_html = fetch_page(embed_url.as_str(), referer).await?;
+        let url = scan(&player_html, embed_url.as_str())?;
+
+        let mut source = StreamSource {
+            url,
+            ..Default::default()
+        };
+        source.headers.insert("Referer".to_string(), embed_url.to_string());
+        Ok(vec![source])
+    }
+}
+
+// Procura a URL do vídeo no HTML do player usando vários padrões
+fn scan(player_html: &str, iframe_url: &str) -> Result<String> {
+    // Buscamos pelo link direto do vídeo no HTML ou JSON do player
+    // Note: Essa parte é instável e pode precisar de atualizações frequentes
+    // conforme o site muda sua estrutura
+    
+    // Vamos tentar diferentes padrões para encontrar a URL do vídeo
+    // 1. Procura pelo padrão "file":"URL_DO_VIDEO"
+    if let Some(pos) = player_html.find("\"file\":\"") {
+        let start = pos + 8; // 8 é o comprimento de "\"file\":\""
+        if let Some(end) = player_html[start..].find("\"") {
+            let video_url = &player_html[start..start + end];
+            // Decodifica sequências de escape JSON (\/)
+            let video_url = video_url.replace("\\/", "/");
+            println!("URL de streaming encontrada (padrão 1): {}", video_url);
+            return Ok(video_url);
+        }
+    }
+    
+    // 2. Procura pelo padrão "src":"URL_DO_VIDEO"
+    if let Some(pos) = player_html.find("\"src\":\"") {
+        let start = pos + 7; // 7 é o comprimento de "\"src\":\""
+        if let Some(end) = player_html[start..].find("\"") {
+            let video_url = &player_html[start..start + end];
+            let video_url = video_url.replace("\\/", "/");
+            println!("URL de streaming encontrada (padrão 2): {}", video_url);
+            return Ok(video_url);
+        }
+    }
+    
+    // 3. Procura por URLs .mp4 ou .m3u8
+    let html_lowercase = player_html.to_lowercase();
+    for pattern in &[".mp4", ".m3u8"] {
+        if let Some(pos) = html_lowercase.find(pattern) {
+            // Retrocedendo até encontrar http ou https
+            let mut start = pos;
+            while start > 0 && !html_lowercase[start-7..start].contains("http") {
+                start -= 1;
+                if start < 7 { break; }
+            }
+            
+            if start >= 7 {
+                let proto_start = html_lowercase[start-7..start].find("http").unwrap() + (start-7);
+                let mut end = pos + pattern.len();
+                
+                // Avança até encontrar aspas, espaço ou >
+                while end < html_lowercase.len() && !"\"> ".contains(&html_lowercase[end..=end]) {
+                    end += 1;
+                }
+                
+                let video_url = &player_html[proto_start..end];
+                println!("URL de streaming encontrada (padrão 3): {}", video_url);
+                return Ok(video_url.to_string());
+            }
+        }
+    }
+    
+    // 4. Se não encontrarmos nada, vamos procurar qualquer URL de outro player
+    let document = Html::parse_document(player_html);
+    let link_selector = Selector::parse("a[href*='streaming'], a[href*='watch'], iframe[src*='embed']").unwrap();
+    
+    if let Some(link) = document.select(&link_selector).next()
+        && let Some(href) = link.value().attr("href").or_else(|| link.value().attr("src")) {
+        println!("Link alternativo encontrado: {}", href);
+        
+        // Se for URL relativa, adiciona o domínio base
+        let video_url = if href.starts_with("http") {
+            href.to_string()
+        } else if href.starts_with("//") {
+            format!("https:{}", href)
+        } else {
+            // Obtém domínio base da iframe_url
+            let base_url = Url::parse(iframe_url)?;
+            let domain = format!("{}://{}", base_url.scheme(), base_url.host_str().unwrap_or(""));
+            format!("{}{}", domain, href)
+        };
+        
+        println!("Redirecionando para: {}", video_url);
+        return Ok(video_url);
+    }
+    
+    // Se chegamos aqui, não conseguimos encontrar a URL do vídeo
+    println!("ERRO: Não foi possível encontrar a URL do vídeo no HTML:");
+    println!("Primeiros 200 caracteres: {}", &player_html[..200.min(player_html.len())]);
+    println!("Últimos 200 caracteres: {}", &player_html[player_html.len() - 200.min(player_html.len())..]);
+    
+    Err(anyhow!("Não foi possível extrair a URL do vídeo"))
+}
@@ -0,0 +1,209 @@
+use aes::Aes256;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use serde::Deserialize;
+use url::Url;
+
+use super::{fetch_page, host_matches, origin, Extractor, USER_AGENT};
+use crate::models::{StreamSource, SubtitleTrack};
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+// Domínios do player próprio do GoGo (Vidstreaming/GogoCDN)
+const DOMAINS: [&str; 8] = [
+    "embtaku.pro",
+    "embtaku.com",
+    "playtaku.net",
+    "playtaku.online",
+    "gogohd.net",
+    "gogohd.pro",
+    "anihdplay.com",
+    "goone.pro",
+];
+
+// Chaves conhecidas, usadas apenas se a página não trouxer as suas
+const FALLBACK_KEY: &str = "37911490979715163134003223491201";
+const FALLBACK_SECOND_KEY: &str = "54674138327930866480207815084989";
+const FALLBACK_IV: &str = "3134003223491201";
+
+/// Extrator do player do GoGo: as fontes vêm de um AJAX criptografado com
+/// AES-256-CBC, cujas chaves ficam escondidas nas classes CSS da página
+pub struct GogoCdn;
+
+// Material criptográfico lido da página do player
+struct Keys {
+    key: String,        // <body class="container-...">: cifra o id e o token
+    second_key: String, // <div class="wrapper container-...">: decifra a resposta
+    iv: String,         // <div class="videocontent videocontent-...">
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxResponse {
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxSources {
+    #[serde(default)]
+    source: Vec<AjaxSource>,
+    #[serde(default)]
+    source_bk: Vec<AjaxSource>,
+    #[serde(default)]
+    track: serde_json::Value, // Objeto `{ tracks: [...] }` ou lista vazia
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxSource {
+    file: String,
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxTrack {
+    file: String,
+    label: Option<String>,
+    kind: Option<String>,
+}
+
+#[async_trait]
+impl Extractor for GogoCdn {
+    fn name(&self) -> &'static str {
+        "GogoCDN"
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        host_matches(host, &DOMAINS) || host.contains("taku") || host.contains("gogo")
+    }
+
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+        let html = fetch_page(embed_url.as_str(), referer).await?;
+        let (keys, encrypted_token) = parse_player(&html)?;
+
+        let id = embed_url
+            .query_pairs()
+            .find(|(k, _)| k == "id")
+            .map(|(_, v)| v.to_string())
+            .ok_or_else(|| anyhow!("ID do vídeo não encontrado na URL do player"))?;
+
+        // O token decifrado já vem no formato de query string ("token=...&expires=...")
+        let token = decrypt(&encrypted_token, &keys.key, &keys.iv)?;
+        let token = token.trim_matches(|c: char| c.is_whitespace() || c.is_control());
+        let encrypted_id = encrypt(&id, &keys.key, &keys.iv)?;
+
+        let ajax_url = format!(
+            "{}encrypt-ajax.php?id={}&alias={}&{}",
+            origin(embed_url),
+            urlencoding::encode(&encrypted_id),
+            id,
+            token
+        );
+
+        let resp = Client::new()
+            .get(&ajax_url)
+            .header("User-Agent", USER_AGENT)
+            .header("Referer", embed_url.as_str())
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!("Falha ao carregar fontes criptografadas: HTTP {}", resp.status()));
+        }
+
+        let ajax: AjaxResponse = resp.json().await?;
+        let json = decrypt(&ajax.data, &keys.second_key, &keys.iv)?;
+        let data: AjaxSources = serde_json::from_str(&json)?;
+
+        let subtitles: Vec<SubtitleTrack> = data
+            .track
+            .get("tracks")
+            .and_then(|t| serde_json::from_value::<Vec<AjaxTrack>>(t.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|t| t.kind.as_deref().is_none_or(|k| k == "captions" || k == "subtitles"))
+            .map(|t| SubtitleTrack { url: t.file, label: t.label })
+            .collect();
+
+        // Fontes principais primeiro, depois as de backup
+        let primary = data.source.into_iter().map(|s| (s, false));
+        let backup = data.source_bk.into_iter().map(|s| (s, true));
+
+        let sources = primary
+            .chain(backup)
+            .map(|(s, is_backup)| {
+                let label = s.label.unwrap_or_else(|| "auto".to_string());
+                let mut source = StreamSource {
+                    url: s.file,
+                    quality: Some(if is_backup { format!("{} (backup)", label) } else { label }),
+                    subtitles: subtitles.clone(),
+                    ..Default::default()
+                };
+                source.headers.insert("Referer".to_string(), origin(embed_url));
+                source
+            })
+            .collect();
+
+        Ok(sources)
+    }
+}
+
+// Lê as chaves e o token criptografado da página do player
+fn parse_player(html: &str) -> Result<(Keys, String)> {
+    let document = Html::parse_document(html);
+
+    let class_suffix = |selector: &str, prefix: &str| -> Option<String> {
+        let selector = Selector::parse(selector).unwrap();
+        document
+            .select(&selector)
+            .next()
+            .and_then(|el| el.value().attr("class"))
+            .and_then(|class| {
+                class
+                    .split_whitespace()
+                    .find_map(|c| c.strip_prefix(prefix))
+                    .map(|s| s.to_string())
+            })
+    };
+
+    let keys = Keys {
+        key: class_suffix("body", "container-").unwrap_or_else(|| FALLBACK_KEY.to_string()),
+        second_key: class_suffix("div.wrapper", "container-").unwrap_or_else(|| FALLBACK_SECOND_KEY.to_string()),
+        iv: class_suffix("div.videocontent", "videocontent-").unwrap_or_else(|| FALLBACK_IV.to_string()),
+    };
+
+    let token_selector = Selector::parse("script[data-name='episode']").unwrap();
+    let token = document
+        .select(&token_selector)
+        .next()
+        .and_then(|el| el.value().attr("data-value"))
+        .ok_or_else(|| anyhow!("Token criptografado não encontrado no player"))?
+        .to_string();
+
+    Ok((keys, token))
+}
+
+// Cifra o texto com AES-256-CBC e retorna em base64
+fn encrypt(text: &str, key: &str, iv: &str) -> Result<String> {
+    let cipher = Aes256CbcEnc::new_from_slices(key.as_bytes(), iv.as_bytes())
+        .map_err(|_| anyhow!("Chave ou IV com tamanho inválido"))?;
+    let encrypted = cipher.encrypt_padded_vec_mut::<Pkcs7>(text.as_bytes());
+    Ok(BASE64.encode(encrypted))
+}
+
+// Decifra um texto em base64 com AES-256-CBC
+fn decrypt(data: &str, key: &str, iv: &str) -> Result<String> {
+    let bytes = BASE64.decode(data.trim())?;
+    let cipher = Aes256CbcDec::new_from_slices(key.as_bytes(), iv.as_bytes())
+        .map_err(|_| anyhow!("Chave ou IV com tamanho inválido"))?;
+    let decrypted = cipher
+        .decrypt_padded_vec_mut::<Pkcs7>(&bytes)
+        .map_err(|_| anyhow!("Falha ao decifrar resposta do player"))?;
+    Ok(String::from_utf8(decrypted)?)
+}
@@ -0,0 +1,158 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::Client;
+use url::Url;
+
+use crate::models::{StreamSource, SubtitleTrack};
+
+mod doodstream;
+mod filelions;
+mod generic;
+mod gogocdn;
+mod mp4upload;
+pub mod packer;
+mod streamwish;
+
+pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// Extrai as fontes de vídeo de um player embutido de um host específico
+#[async_trait]
+pub trait Extractor: Send + Sync {
+    /// Nome do extrator, usado nas mensagens
+    fn name(&self) -> &'static str;
+
+    /// Indica se o extrator sabe lidar com o host do iframe
+    fn matches(&self, host: &str) -> bool;
+
+    /// Extrai as fontes de vídeo da página do player
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>>;
+}
+
+// Extratores disponíveis, em ordem de prioridade. O genérico fica por último
+// e aceita qualquer host
+pub fn extractors() -> Vec<Box<dyn Extractor>> {
+    vec![
+        Box::new(gogocdn::GogoCdn),
+        Box::new(streamwish::StreamWish),
+        Box::new(filelions::FileLions),
+        Box::new(doodstream::DoodStream),
+        Box::new(mp4upload::Mp4Upload),
+        Box::new(generic::Generic),
+    ]
+}
+
+// Escolhe o extrator pelo host do iframe
+pub fn for_host(host: &str) -> Box<dyn Extractor> {
+    extractors()
+        .into_iter()
+        .find(|e| e.matches(host))
+        .unwrap_or_else(|| Box::new(generic::Generic))
+}
+
+// Extrai as fontes de vídeo de um player embutido
+pub async fn extract(embed_url: &str, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+    let url = Url::parse(embed_url)?;
+    let host = url.host_str().unwrap_or("").to_lowercase();
+    let extractor = for_host(&host);
+
+    println!("Usando extrator {} para {}", extractor.name(), host);
+    let sources = extractor.extract(&url, referer).await?;
+
+    if sources.is_empty() {
+        return Err(anyhow!("Extrator {} não encontrou nenhuma fonte", extractor.name()));
+    }
+    Ok(sources)
+}
+
+// Verifica se o host é um dos domínios informados ou um subdomínio deles
+pub fn host_matches(host: &str, domains: &[&str]) -> bool {
+    domains
+        .iter()
+        .any(|d| host == *d || host.ends_with(&format!(".{}", d)) || host.starts_with(&format!("{}.", d)))
+}
+
+// Baixa a página do player, enviando o Referer quando informado. Scripts
+// compactados com P.A.C.K.E.R. já são expandidos aqui
+pub async fn fetch_page(url: &str, referer: Option<&str>) -> Result<String> {
+    let mut request = Client::new()
+        .get(url)
+        .header("User-Agent", USER_AGENT);
+    if let Some(referer) = referer {
+        request = request.header("Referer", referer);
+    }
+
+    let resp = request.send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Falha ao carregar player: HTTP {}", resp.status()));
+    }
+
+    let html = resp.text().await?;
+    if packer::is_packed(&html) {
+        println!("Expandindo scripts compactados do player...");
+        return Ok(packer::unpack_all(&html));
+    }
+    Ok(html)
+}
+
+// Retorna a origem da URL com barra final ("https://host/"), usada como Referer
+pub fn origin(url: &Url) -> String {
+    format!("{}://{}/", url.scheme(), url.host_str().unwrap_or(""))
+}
+
+// Extrai as listas `sources` e `tracks` de uma configuração do jwplayer:
+//   sources: [{file:"https://.../master.m3u8", label:"1080p"}],
+//   tracks: [{file:"https://.../eng.vtt", label:"English", kind:"captions"}]
+pub fn jwplayer_sources(html: &str) -> Vec<StreamSource> {
+    let sources_re = Regex::new(r"(?s)sources\s*:\s*\[(.*?)\]").unwrap();
+    let tracks_re = Regex::new(r"(?s)tracks\s*:\s*\[(.*?)\]").unwrap();
+
+    let subtitles: Vec<SubtitleTrack> = tracks_re
+        .captures(html)
+        .map(|caps| {
+            js_objects(&caps[1])
+                .into_iter()
+                .filter(|obj| {
+                    let kind = js_field(obj, "kind").unwrap_or_default();
+                    kind.is_empty() || kind == "captions" || kind == "subtitles"
+                })
+                .filter_map(|obj| {
+                    Some(SubtitleTrack {
+                        url: js_field(&obj, "file")?,
+                        label: js_field(&obj, "label"),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    sources_re
+        .captures_iter(html)
+        .flat_map(|caps| js_objects(&caps[1]))
+        .filter_map(|obj| {
+            Some(StreamSource {
+                url: js_field(&obj, "file")?,
+                quality: js_field(&obj, "label"),
+                subtitles: subtitles.clone(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+// Separa os objetos `{...}` de uma lista JavaScript
+fn js_objects(list: &str) -> Vec<String> {
+    let object_re = Regex::new(r"(?s)\{(.*?)\}").unwrap();
+    object_re
+        .captures_iter(list)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+// Lê o valor de um campo de um objeto JavaScript/JSON: file:"..." ou "file":'...'
+fn js_field(object: &str, field: &str) -> Option<String> {
+    let field_re = Regex::new(&format!(r#"["']?{}["']?\s*:\s*["']([^"']*)["']"#, field)).unwrap();
+    field_re
+        .captures(object)
+        .map(|caps| caps[1].replace("\\/", "/"))
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use url::Url;
+
+use super::{fetch_page, host_matches, Extractor};
+use crate::models::StreamSource;
+
+/// Extrator do Mp4Upload: o link fica em `player.src({ src: "..." })`
+pub struct Mp4Upload;
+
+#[async_trait]
+impl Extractor for Mp4Upload {
+    fn name(&self) -> &'static str {
+        "Mp4Upload"
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        host_matches(host, &["mp4upload.com"])
+    }
+
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+        let html = fetch_page(embed_url.as_str(), referer).await?;
+
+        let src_re = Regex::new(r#"src\s*:\s*"(https?://[^"]+\.mp4[^"]*)""#).unwrap();
+        let sources = src_re
+            .captures_iter(&html)
+            .map(|caps| {
+                let mut source = StreamSource {
+                    url: caps[1].to_string(),
+                    ..Default::default()
+                };
+                // Sem esse Referer o servidor responde 403
+                source.headers.insert("Referer".to_string(), "https://www.mp4upload.com/".to_string());
+                source
+            })
+            .collect();
+
+        Ok(sources)
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{Result, anyhow};
+use regex::{Captures, Regex};
+
+// Alfabeto usado pelo P.A.C.K.E.R. para codificar os índices das palavras
+const ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Assinatura dos scripts gerados pelo packer do Dean Edwards:
+//   eval(function(p,a,c,k,e,d){...}('payload',62,123,'palavra|outra'.split('|'),0,{}))
+const SIGNATURE: &str = "eval(function(p,a,c,k,e,";
+
+// Indica se o HTML contém algum script compactado
+pub fn is_packed(html: &str) -> bool {
+    html.contains(SIGNATURE)
+}
+
+// Expande todos os scripts compactados do HTML e os anexa ao final do
+// documento, para que os extratores enxerguem as `sources` escondidas
+pub fn unpack_all(html: &str) -> String {
+    let scripts = unpack_scripts(html);
+    if scripts.is_empty() {
+        return html.to_string();
+    }
+
+    let mut expanded = html.to_string();
+    for script in scripts {
+        expanded.push_str("\n<script>");
+        expanded.push_str(&script);
+        expanded.push_str("</script>");
+    }
+    expanded
+}
+
+// Expande cada script compactado encontrado no HTML
+pub fn unpack_scripts(html: &str) -> Vec<String> {
+    html.match_indices(SIGNATURE)
+        .filter_map(|(pos, _)| match unpack(&html[pos..]) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                println!("Falha ao expandir script compactado: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Expande o primeiro script compactado do texto
+pub fn unpack(packed: &str) -> Result<String> {
+    let args_re = [
+        Regex::new(r#"(?s)\}\s*\(\s*'((?:[^'\\]|\\.)*)'\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*'((?:[^'\\]|\\.)*)'\.split\(\s*'\|'\s*\)"#).unwrap(),
+        Regex::new(r#"(?s)\}\s*\(\s*"((?:[^"\\]|\\.)*)"\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*"((?:[^"\\]|\\.)*)"\.split\(\s*"\|"\s*\)"#).unwrap(),
+    ];
+
+    let caps = args_re
+        .iter()
+        .find_map(|re| re.captures(packed))
+        .ok_or_else(|| anyhow!("Argumentos do packer não encontrados"))?;
+
+    let payload = unescape(&caps[1]);
+    let radix: usize = caps[2].parse()?;
+    let count: usize = caps[3].parse()?;
+    let keywords: Vec<String> = unescape(&caps[4]).split('|').map(|s| s.to_string()).collect();
+
+    if radix < 2 || radix > ALPHABET.len() {
+        return Err(anyhow!("Base {} não suportada pelo unpacker", radix));
+    }
+    if keywords.len() != count {
+        return Err(anyhow!("Número de palavras incorreto: esperado {}, encontrado {}", count, keywords.len()));
+    }
+
+    // Cada palavra do payload é um índice na base informada; substitui pelo
+    // valor correspondente da lista, mantendo a palavra se a entrada for vazia
+    let word_re = Regex::new(r"\b\w+\b").unwrap();
+    let unpacked = word_re.replace_all(&payload, |caps: &Captures| {
+        let word = &caps[0];
+        match unbase(word, radix) {
+            Some(index) if index < keywords.len() && !keywords[index].is_empty() => keywords[index].clone(),
+            _ => word.to_string(),
+        }
+    });
+
+    Ok(unpacked.into_owned())
+}
+
+// Converte uma palavra codificada na base informada para o índice da lista
+fn unbase(word: &str, radix: usize) -> Option<usize> {
+    let alphabet = &ALPHABET[..radix];
+    word.chars().try_fold(0usize, |acc, c| {
+        let digit = alphabet.find(c)?;
+        acc.checked_mul(radix)?.checked_add(digit)
+    })
+}
+
+// Remove os escapes de uma string JavaScript entre aspas
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(next @ ('\'' | '"' | '\\')) => result.push(next),
+            Some(next) => {
+                result.push('\\');
+                result.push(next);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::{filelions::links_sources, jwplayer_sources};
+    use std::path::{Path, PathBuf};
+    use url::Url;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/packer")
+    }
+
+    fn read_fixture(name: &str) -> String {
+        std::fs::read_to_string(fixtures().join(name)).unwrap()
+    }
+
+    // Cada página salva em fixtures/packer tem o script esperado ao lado
+    #[test]
+    fn unpacks_saved_player_pages() {
+        let mut pages: Vec<PathBuf> = std::fs::read_dir(fixtures())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+            .collect();
+        pages.sort();
+        assert!(!pages.is_empty());
+
+        for page in pages {
+            let html = std::fs::read_to_string(&page).unwrap();
+            assert!(is_packed(&html), "{}", page.display());
+            let expected = std::fs::read_to_string(page.with_extension("expected.js")).unwrap();
+            assert_eq!(unpack_scripts(&html).join("\n"), expected.trim(), "{}", page.display());
+        }
+    }
+
+    #[test]
+    fn finds_streamwish_sources() {
+        let sources = jwplayer_sources(&unpack_all(&read_fixture("streamwish.html")));
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].url.starts_with("https://s1.wishcdn.example/hls2/"));
+        assert!(sources[0].url.contains("/master.m3u8?"));
+
+        // A trilha de miniaturas não é uma legenda
+        let subtitles = &sources[0].subtitles;
+        assert_eq!(subtitles.len(), 1);
+        assert_eq!(subtitles[0].url, "https://s1.wishcdn.example/subs/abcdef_eng.vtt");
+        assert_eq!(subtitles[0].label.as_deref(), Some("English"));
+        assert_eq!(subtitles[0].language(), Some("en"));
+    }
+
+    #[test]
+    fn finds_filelions_links() {
+        let html = unpack_all(&read_fixture("filelions.html"));
+        assert!(jwplayer_sources(&html).is_empty());
+
+        let embed = Url::parse("https://filelions.example/v/q9w8e7r6t5y4").unwrap();
+        let urls: Vec<String> = links_sources(&html, &embed).into_iter().map(|s| s.url).collect();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[0], "https://filelions.example/stream/Q9w8E7/master.m3u8");
+        assert!(urls[1].starts_with("https://lions.cdn.example/hls2/"));
+    }
+}
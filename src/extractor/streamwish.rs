@@ -0,0 +1,44 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use url::Url;
+
+use super::{fetch_page, host_matches, jwplayer_sources, origin, Extractor};
+use crate::models::StreamSource;
+
+// Domínios usados pelo StreamWish e seus espelhos
+const DOMAINS: [&str; 9] = [
+    "streamwish.com",
+    "streamwish.to",
+    "awish.pro",
+    "dwish.pro",
+    "embedwish.com",
+    "wishembed.pro",
+    "strwish.com",
+    "mwish.pro",
+    "playerwish.com",
+];
+
+/// Extrator do StreamWish: o player é um jwplayer com `sources` e `tracks`
+pub struct StreamWish;
+
+#[async_trait]
+impl Extractor for StreamWish {
+    fn name(&self) -> &'static str {
+        "StreamWish"
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        host_matches(host, &DOMAINS) || host.contains("wish")
+    }
+
+    async fn extract(&self, embed_url: &Url, referer: Option<&str>) -> Result<Vec<StreamSource>> {
+        let html = fetch_page(embed_url.as_str(), referer).await?;
+
+        // O servidor de vídeo só aceita requisições vindas do próprio player
+        let mut sources = jwplayer_sources(&html);
+        for source in &mut sources {
+            source.headers.insert("Referer".to_string(), origin(embed_url));
+        }
+        Ok(sources)
+    }
+}
@@ -0,0 +1,335 @@
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::path::PathBuf;
+
+use crate::hls::is_hls;
+use crate::models::{Audio, EpisodeNumber};
+use crate::utils::get_filename_from_url;
+
+/// Modelo padrão: uma pasta por anime e o episódio no formato reconhecido por
+/// servidores de mídia (Jellyfin, Plex, Kodi...)
+pub const DEFAULT_TEMPLATE: &str = "{title}/{title} - S{season:02}E{episode:02} [{quality}].{ext}";
+
+// Campos aceitos no modelo, para a mensagem de erro
+const FIELDS: &str = "title, series, season, episode, episode_title, quality, audio, provider, ext";
+
+// Maior tamanho de um nome de arquivo ou pasta (a maioria dos sistemas de
+// arquivos aceita 255 bytes)
+const MAX_COMPONENT_BYTES: usize = 200;
+
+/// Dados do episódio usados para montar o nome do arquivo
+pub struct FileInfo<'a> {
+    pub title: &'a str,
+    pub season: Option<u32>, // Sem valor, é detectada pelo título (padrão 1)
+    pub episode: &'a EpisodeNumber,
+    pub episode_title: Option<&'a str>,
+    pub quality: Option<&'a str>,
+    pub audio: Audio,
+    pub provider: &'a str,
+    pub ext: &'a str,
+}
+
+impl FileInfo<'_> {
+    // Temporada do episódio. Especiais ficam na temporada 0, como os
+    // servidores de mídia esperam
+    fn season(&self) -> u32 {
+        if matches!(self.episode, EpisodeNumber::Special(_)) {
+            return 0;
+        }
+        self.season.or_else(|| title_season(self.title)).unwrap_or(1)
+    }
+}
+
+/// Monta o caminho relativo do arquivo a partir do modelo. `/` separa pastas;
+/// cada campo é `{nome}` ou `{nome:02}` (números com zeros à esquerda) e `{{`
+/// e `}}` escrevem chaves. Os valores são limpos para valerem como nome de
+/// arquivo em qualquer sistema, e `.{ext}` é acrescentado se faltar
+pub fn render(template: &str, info: &FileInfo) -> Result<PathBuf> {
+    let mut template = template.trim().to_string();
+    if !template.contains("{ext}") {
+        template.push_str(".{ext}");
+    }
+
+    let components: Vec<&str> = template.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+    let mut path = PathBuf::new();
+    for (idx, component) in components.iter().enumerate() {
+        let expanded = expand(component, info)?;
+        let is_file = idx + 1 == components.len();
+        path.push(sanitize_component(&expanded, is_file.then_some(info.ext)));
+    }
+    Ok(path)
+}
+
+/// Confere o modelo antes de começar (campos e formatos válidos)
+pub fn validate(template: &str) -> Result<()> {
+    let episode = EpisodeNumber::Numeric { whole: 1, fraction: None };
+    let sample = FileInfo {
+        title: "Anime",
+        season: None,
+        episode: &episode,
+        episode_title: None,
+        quality: None,
+        audio: Audio::Sub,
+        provider: "",
+        ext: "mp4",
+    };
+    render(template, &sample).map(|_| ())
+}
+
+// Substitui os campos de um trecho do modelo
+fn expand(component: &str, info: &FileInfo) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = component.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (name, width) = match spec.split_once(':') {
+                    Some((name, format)) => {
+                        let width = format
+                            .parse::<usize>()
+                            .map_err(|_| anyhow!("Formato inválido em {{{}}}: use {{{}:02}}", spec, name))?;
+                        (name.trim(), width)
+                    }
+                    None => (spec.trim(), 0),
+                };
+                out.push_str(&field(name, width, info)?);
+            }
+            '}' => return Err(anyhow!("\"}}\" sem \"{{\" no modelo: {}", component)),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+// Valor de um campo do modelo. `width` completa números com zeros
+fn field(name: &str, width: usize, info: &FileInfo) -> Result<String> {
+    let value = match name {
+        "title" => clean_value(info.title),
+        "series" => clean_value(&series_title(info.title)),
+        "season" => format!("{:0width$}", info.season()),
+        "episode" => match info.episode {
+            EpisodeNumber::Numeric { whole, fraction: Some(fraction) } => format!("{:0width$}.{}", whole, fraction),
+            EpisodeNumber::Numeric { whole, fraction: None } => format!("{:0width$}", whole),
+            EpisodeNumber::Special(label) => clean_value(label),
+        },
+        "episode_title" => clean_value(info.episode_title.unwrap_or_default()),
+        "quality" => clean_value(info.quality.unwrap_or_default()),
+        "audio" => info.audio.to_string(),
+        "provider" => clean_value(info.provider),
+        "ext" => info.ext.to_string(),
+        _ => return Err(anyhow!("Campo desconhecido no modelo: {{{}}} (use: {})", name, FIELDS)),
+    };
+    Ok(value)
+}
+
+// Limpa o valor de um campo: barras viram hífen para não criar pastas
+// (ex: "Fate/Zero")
+fn clean_value(value: &str) -> String {
+    value.replace(['/', '\\'], "-").trim().to_string()
+}
+
+/// Ajusta um nome de pasta ou arquivo: troca caracteres proibidos no Windows,
+/// remove colchetes vazios (campos sem valor), espaços repetidos e pontos no
+/// fim, evita nomes reservados e limita o tamanho. Em arquivos, `ext` é
+/// preservada ao cortar o nome
+pub fn sanitize_component(name: &str, ext: Option<&str>) -> String {
+    let (stem, ext) = match ext {
+        Some(ext) if !ext.is_empty() => match name.strip_suffix(ext).and_then(|s| s.strip_suffix('.')) {
+            Some(stem) => (stem, Some(ext)),
+            None => (name, None),
+        },
+        _ => (name, None),
+    };
+
+    let mut clean: String = stem
+        .replace(": ", " - ")
+        .chars()
+        .map(|c| match c {
+            ':' => '-',
+            '<' | '>' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    clean = clean.replace("[]", "").replace("()", "");
+    clean = clean.split_whitespace().collect::<Vec<_>>().join(" ");
+    clean = clean
+        .trim_matches(|c: char| c == '-' || c == '_' || c.is_whitespace())
+        .trim_end_matches('.')
+        .to_string();
+
+    // Nomes de dispositivo do Windows não podem ser usados nem com extensão
+    let reserved = ["CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "LPT1", "LPT2", "LPT3"];
+    if clean.is_empty() || clean.chars().all(|c| c == '.') {
+        clean = "_".to_string();
+    } else if reserved.iter().any(|r| clean.eq_ignore_ascii_case(r)) {
+        clean.insert(0, '_');
+    }
+
+    let suffix = ext.map(|ext| format!(".{}", ext)).unwrap_or_default();
+    let limit = MAX_COMPONENT_BYTES.saturating_sub(suffix.len());
+    if clean.len() > limit {
+        let mut end = limit;
+        while !clean.is_char_boundary(end) {
+            end -= 1;
+        }
+        clean.truncate(end);
+        clean = clean.trim_end().trim_end_matches('.').to_string();
+    }
+    clean + &suffix
+}
+
+/// Extensão do arquivo baixado a partir da URL. Playlists HLS viram `.mp4`
+/// (ou `.ts`, sem o ffmpeg)
+pub fn extension_for(url: &str) -> String {
+    if is_hls(url) {
+        return "mp4".to_string();
+    }
+    get_filename_from_url(url)
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .filter(|ext| !ext.is_empty() && ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "mp4".to_string())
+}
+
+// Temporada indicada no título: "Season 2", "2nd Season", "S2", "Temporada 2"
+fn season_regex() -> Regex {
+    Regex::new(r"(?i)[\s:\-]*\b(?:season\s*(\d+)|(\d+)(?:st|nd|rd|th)\s+season|s(\d{1,2})|temporada\s*(\d+)|(\d+)ª?\s+temporada)\b")
+        .unwrap()
+}
+
+// Temporada informada no título, se houver
+fn title_season(title: &str) -> Option<u32> {
+    let caps = season_regex().captures(title)?;
+    caps.iter().skip(1).flatten().next()?.as_str().parse().ok()
+}
+
+// Título sem a indicação de temporada ("Frieren Season 2" -> "Frieren"),
+// para agrupar as temporadas na mesma pasta
+fn series_title(title: &str) -> String {
+    let series = season_regex().replace(title, "");
+    let series = series.trim();
+    if series.is_empty() { title.trim().to_string() } else { series.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn info<'a>(title: &'a str, episode: &'a EpisodeNumber) -> FileInfo<'a> {
+        FileInfo {
+            title,
+            season: None,
+            episode,
+            episode_title: Some("A Aventura Termina"),
+            quality: Some("1080p"),
+            audio: Audio::Sub,
+            provider: "gogoanime",
+            ext: "mp4",
+        }
+    }
+
+    fn render_with(template: &str, info: &FileInfo) -> String {
+        render(template, info).unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn default_template() {
+        let episode: EpisodeNumber = "3".parse().unwrap();
+        let path = render(DEFAULT_TEMPLATE, &info("Frieren", &episode)).unwrap();
+        assert_eq!(path, Path::new("Frieren/Frieren - S01E03 [1080p].mp4"));
+
+        let path = render(DEFAULT_TEMPLATE, &info("Frieren Season 2", &episode)).unwrap();
+        assert_eq!(path, Path::new("Frieren Season 2/Frieren Season 2 - S02E03 [1080p].mp4"));
+    }
+
+    #[test]
+    fn episode_padding_fractions_and_specials() {
+        let fraction: EpisodeNumber = "5.5".parse().unwrap();
+        assert_eq!(render_with("S{season:02}E{episode:02}", &info("Frieren", &fraction)), "S01E05.5.mp4");
+
+        let special: EpisodeNumber = "OVA".parse().unwrap();
+        assert_eq!(render_with("S{season:02}E{episode:02}", &info("Frieren", &special)), "S00EOVA.mp4");
+
+        let mut with_season = info("Frieren", &fraction);
+        with_season.season = Some(3);
+        assert_eq!(render_with("{season}x{episode:03}", &with_season), "3x005.5.mp4");
+    }
+
+    #[test]
+    fn empty_fields_and_unsafe_values() {
+        let episode: EpisodeNumber = "1".parse().unwrap();
+        let mut fate = info("Fate/Zero", &episode);
+        fate.quality = None;
+        assert_eq!(render(DEFAULT_TEMPLATE, &fate).unwrap(), Path::new("Fate-Zero/Fate-Zero - S01E01.mp4"));
+        assert_eq!(render(DEFAULT_TEMPLATE, &fate).unwrap().components().count(), 2);
+
+        let re_zero = info("Re:Zero - Starting Life: Part 2?", &episode);
+        assert_eq!(render_with("{title}", &re_zero), "Re-Zero - Starting Life - Part 2.mp4");
+        assert_eq!(render_with("{title}", &info("What? <Why> \"Who\"", &episode)), "What_ _Why_ _Who.mp4");
+    }
+
+    #[test]
+    fn dots_and_reserved_names() {
+        assert_eq!(sanitize_component("..", None), "_");
+        assert_eq!(sanitize_component(".", None), "_");
+        assert_eq!(sanitize_component("Anime...", None), "Anime");
+        assert_eq!(sanitize_component("CON", None), "_CON");
+        assert_eq!(sanitize_component("nul.mp4", Some("mp4")), "_nul.mp4");
+        assert_eq!(sanitize_component(".mp4", Some("mp4")), "_.mp4");
+
+        let episode: EpisodeNumber = "1".parse().unwrap();
+        let path = render("{title}/../{episode}", &info("..", &episode)).unwrap();
+        assert!(path.components().all(|c| matches!(c, std::path::Component::Normal(_))), "{:?}", path);
+    }
+
+    #[test]
+    fn long_names_keep_the_extension() {
+        let long = format!("{}.mp4", "á".repeat(150));
+        let clean = sanitize_component(&long, Some("mp4"));
+        assert!(clean.len() <= MAX_COMPONENT_BYTES);
+        assert!(clean.ends_with("á.mp4"));
+
+        let folder = sanitize_component(&"x".repeat(300), None);
+        assert_eq!(folder.len(), MAX_COMPONENT_BYTES);
+    }
+
+    #[test]
+    fn escapes_and_errors() {
+        let episode: EpisodeNumber = "7".parse().unwrap();
+        assert_eq!(render_with("{{{episode}}}", &info("Frieren", &episode)), "{7}.mp4");
+        assert_eq!(render_with("{title}.{ext}", &info("Frieren", &episode)), "Frieren.mp4");
+
+        assert!(validate(DEFAULT_TEMPLATE).is_ok());
+        let error = validate("{title} {resolution}").unwrap_err().to_string();
+        assert!(error.contains("{resolution}"), "{}", error);
+        assert!(validate("{episode:xx}").is_err());
+        assert!(validate("{title}}").is_err());
+    }
+
+    #[test]
+    fn season_from_title() {
+        assert_eq!(title_season("Frieren Season 2"), Some(2));
+        assert_eq!(title_season("Shingeki no Kyojin 3rd Season"), Some(3));
+        assert_eq!(title_season("Dr. Stone S3"), Some(3));
+        assert_eq!(title_season("Haikyuu Temporada 4"), Some(4));
+        assert_eq!(title_season("Haikyuu 2ª Temporada"), Some(2));
+        assert_eq!(title_season("Frieren"), None);
+        assert_eq!(title_season("Mob Psycho 100"), None);
+
+        assert_eq!(series_title("Frieren Season 2"), "Frieren");
+        assert_eq!(series_title("Dr. Stone: S3"), "Dr. Stone");
+        assert_eq!(series_title("Season 2"), "Season 2");
+    }
+}
@@ -1,24 +1,40 @@
 use anyhow::{Result, anyhow};
+use async_trait::async_trait;
 use reqwest::Client;
 use scraper::{Html, Selector};
-use serde::{Serialize, Deserialize};
-use url::Url;
+
+use crate::extractor::{self, USER_AGENT};
+use crate::models::{pair_variants, AnimeItem, EpisodeItem, EpisodeNumber, ServerKind, StreamServer, StreamSource};
+use crate::provider::AnimeProvider;
 
 const GOGOANIME_URL: &str = "https://gogoanime3.cc";
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+const GOGOANIME_AJAX_URL: &str = "https://ajax.gogocdn.net";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AnimeItem {
-    pub id: String,       // URL slug para o anime
-    pub title: String,    // Título visível do anime
-    pub image: String,    // URL da imagem de capa (opcional)
-}
+/// Provedor que faz scraping direto do site do GoGoAnime
+pub struct GogoAnimeProvider;
+
+#[async_trait]
+impl AnimeProvider for GogoAnimeProvider {
+    fn name(&self) -> &'static str {
+        "gogoanime"
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EpisodeItem {
-    pub id: String,       // ID do episódio para streaming
-    pub number: String,   // Número do episódio
-    pub title: Option<String>, // Título do episódio (se disponível)
+    async fn search(&self, query: &str) -> Result<Vec<AnimeItem>> {
+        search_anime(query).await
+    }
+
+    async fn episodes(&self, anime_id: &str) -> Result<Vec<EpisodeItem>> {
+        get_episodes(anime_id).await
+    }
+
+    async fn servers(&self, episode_id: &str) -> Result<Vec<StreamServer>> {
+        get_servers(episode_id).await
+    }
+
+    async fn resolve(&self, server: &StreamServer) -> Result<Vec<StreamSource>> {
+        println!("Extraindo URL de streaming de {}: {}", server.name, server.url);
+        extractor::extract(&server.url, server.referer.as_deref()).await
+    }
 }
 
 /// Função para buscar animes pelo nome
@@ -71,6 +87,7 @@ pub async fn search_anime(query: &str) -> Result<Vec<AnimeItem>> {
                     id,
                     title,
                     image,
+                    variants: Vec::new(),
                 });
             }
         }
@@ -81,7 +98,7 @@ pub async fn search_anime(query: &str) -> Result<Vec<AnimeItem>> {
     }
     
     println!("Encontrados {} resultados", results.len());
-    Ok(results)
+    Ok(pair_variants(results))
 }
 
 /// Função para obter a lista de episódios
@@ -104,56 +121,146 @@ pub async fn get_episodes(anime_id: &str) -> Result<Vec<EpisodeItem>> {
     }
     
     let html = resp.text().await?;
-    let document = Html::parse_document(&html);
+    let (movie_id, ranges) = parse_anime_page(&html)?;
+    
+    // O alias é o slug do anime, sem o prefixo "category/"
+    let alias = anime_id.trim_start_matches("category/");
+    
+    // A lista completa vem do endpoint AJAX, uma requisição por faixa de episódios.
+    // As faixas vêm em ordem crescente e cada resposta em ordem decrescente, então
+    // percorremos as faixas de trás para frente para manter os mais recentes primeiro
+    let mut episodes: Vec<EpisodeItem> = Vec::new();
+    for (ep_start, ep_end) in ranges.iter().rev() {
+        let list_url = format!(
+            "{}/ajax/load-list-episode?ep_start={}&ep_end={}&id={}&default_ep=0&alias={}",
+            GOGOANIME_AJAX_URL, ep_start, ep_end, movie_id, alias
+        );
+        
+        let resp = client
+            .get(&list_url)
+            .header("User-Agent", USER_AGENT)
+            .header("Referer", &anime_url)
+            .send()
+            .await?;
+        
+        if !resp.status().is_success() {
+            return Err(anyhow!("Falha ao carregar lista de episódios ({}-{}): HTTP {}", ep_start, ep_end, resp.status()));
+        }
+        
+        let list_html = resp.text().await?;
+        for episode in parse_episode_list(&list_html) {
+            // Faixas podem se sobrepor nas bordas
+            if !episodes.iter().any(|e| e.id == episode.id) {
+                episodes.push(episode);
+            }
+        }
+    }
+    
+    println!("Total de episódios encontrados: {}", episodes.len());
+    
+    if episodes.is_empty() {
+        return Err(anyhow!("Nenhum episódio encontrado para este anime"));
+    }
+    
+    Ok(episodes)
+}
+
+// Extrai o `#movie_id` e as faixas de episódios (ep_start, ep_end) da página do anime
+fn parse_anime_page(html: &str) -> Result<(String, Vec<(String, String)>)> {
+    let document = Html::parse_document(html);
     
     // Precisamos pegar o ID do anime no GoGoAnime para acessar a lista de episódios
     let id_selector = Selector::parse("#movie_id").unwrap();
-    let anime_id_value = document
+    let movie_id = document
         .select(&id_selector)
         .next()
         .and_then(|el| el.value().attr("value"))
-        .ok_or_else(|| anyhow!("ID do anime não encontrado na página"))?;
+        .ok_or_else(|| anyhow!("ID do anime não encontrado na página"))?
+        .to_string();
+    
+    let range_selector = Selector::parse("#episode_page a").unwrap();
+    let mut ranges: Vec<(String, String)> = document
+        .select(&range_selector)
+        .filter_map(|el| {
+            let start = el.value().attr("ep_start")?;
+            let end = el.value().attr("ep_end")?;
+            Some((start.to_string(), end.to_string()))
+        })
+        .collect();
+    
+    // Sem paginação na página, pede tudo em uma única faixa
+    if ranges.is_empty() {
+        ranges.push(("0".to_string(), "9999".to_string()));
+    }
+    
+    Ok((movie_id, ranges))
+}
 
-    let ep_start_selector = Selector::parse("#episode_page a.active").unwrap();
-    let total_eps = document
-        .select(&ep_start_selector)
-        .next()
-        .and_then(|el| el.value().attr("ep_end"))
-        .and_then(|ep| ep.parse::<i32>().ok())
-        .unwrap_or(1);
-        
-    println!("Total de episódios encontrados: {}", total_eps);
+// Converte o HTML retornado pelo endpoint AJAX em episódios
+// Número do episódio no fim do slug. A fração vem com hífen, que vira ponto:
+// "slug-episode-12-5" -> "12.5"
+fn slug_episode(id: &str) -> String {
+    let suffix = id.rsplit("-episode-").next().unwrap_or(id);
+    match suffix.split_once('-') {
+        Some((whole, fraction))
+            if !whole.is_empty()
+                && !fraction.is_empty()
+                && whole.chars().all(|c| c.is_ascii_digit())
+                && fraction.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            format!("{}.{}", whole, fraction)
+        }
+        _ => suffix.to_string(),
+    }
+}
+
+fn parse_episode_list(html: &str) -> Vec<EpisodeItem> {
+    let fragment = Html::parse_fragment(html);
+    let item_selector = Selector::parse("li a").unwrap();
+    let name_selector = Selector::parse("div.name").unwrap();
     
-    // Agora podemos montar a lista de episódios manualmente
     let mut episodes = Vec::new();
-    for ep_num in 1..=total_eps {
-        // Corrigindo o formato do ID do episódio para corresponder ao padrão do site
-        let episode_id = if anime_id.starts_with("category/") {
-            format!("{}-episode-{}", anime_id.trim_start_matches("category/"), ep_num)
+    for link in fragment.select(&item_selector) {
+        let Some(href) = link.value().attr("href") else {
+            continue;
+        };
+        
+        // O href vem com espaços e barra inicial: " /slug-episode-12-5"
+        let id = href.trim().trim_start_matches('/').to_string();
+        if id.is_empty() {
+            continue;
+        }
+        
+        // O número fica no texto do div.name: "EP 12.5". Se não houver,
+        // usa o sufixo do slug: "slug-episode-12-5"
+        let label = link
+            .select(&name_selector)
+            .next()
+            .map(|el| el.text().collect::<String>())
+            .unwrap_or_default();
+        let label = if label.trim().is_empty() {
+            slug_episode(&id)
         } else {
-            format!("{}-episode-{}", anime_id, ep_num)
+            label
+        };
+        
+        let Ok(number) = label.parse::<EpisodeNumber>() else {
+            continue;
         };
         
         episodes.push(EpisodeItem {
-            id: episode_id.clone(),
-            number: ep_num.to_string(),
+            id,
+            number,
             title: None, // GoGoAnime normalmente não tem títulos dos episódios
         });
     }
     
-    // Ordenar episódios em ordem decrescente (mais recentes primeiro)
-    episodes.reverse();
-    
-    if episodes.is_empty() {
-        return Err(anyhow!("Nenhum episódio encontrado para este anime"));
-    }
-    
-    Ok(episodes)
+    episodes
 }
 
-/// Função para extrair a URL de streaming
-pub async fn get_stream_url(episode_id: &str) -> Result<String> {
-    println!("Extraindo URL de streaming para: {}", episode_id);
+/// Função para listar os servidores de vídeo de um episódio
+pub async fn get_servers(episode_id: &str) -> Result<Vec<StreamServer>> {
+    println!("Buscando servidores para: {}", episode_id);
     
     // Ajustando o formato da URL do episódio para corresponder ao padrão do site
     let episode_url = if episode_id.contains("/") {
@@ -177,143 +284,101 @@ pub async fn get_stream_url(episode_id: &str) -> Result<String> {
     }
     
     let html = resp.text().await?;
-    let document = Html::parse_document(&html);
+    let servers = parse_servers(&html, &episode_url);
     
-    // Encontra o iframe do player (tentando diferentes seletores)
-    let iframe_selectors = [
-        Selector::parse("div.play-video iframe").unwrap(),
-        Selector::parse("iframe#player").unwrap(),
-        Selector::parse("iframe[src*='streaming']").unwrap(),
-        Selector::parse("iframe").unwrap(),
-    ];
-    
-    let mut iframe_src = None;
-    for selector in &iframe_selectors {
-        if let Some(iframe) = document.select(selector).next() {
-            if let Some(src) = iframe.value().attr("src") {
-                iframe_src = Some(src.to_string());
-                println!("Iframe encontrado com seletor: {:?}", selector);
-                break;
-            }
-        }
+    if servers.is_empty() {
+        return Err(anyhow!("Nenhum servidor de vídeo encontrado na página do episódio"));
     }
     
-    let iframe_src = iframe_src.ok_or_else(|| {
-        // Se não encontrarmos o iframe, vamos mostrar a estrutura do HTML para debug
-        println!("Estrutura HTML da página:");
-        document.select(&Selector::parse("body").unwrap()).for_each(|el| {
-            println!("{:?}", el.html());
-        });
-        anyhow!("Player iframe não encontrado")
-    })?;
-    
-    println!("URL do iframe: {}", iframe_src);
-    
-    // Se o iframe_src não tiver schema (começando com //), adiciona https:
-    let iframe_url = if iframe_src.starts_with("//") {
-        format!("https:{}", iframe_src)
-    } else {
-        iframe_src.to_string()
-    };
-    
-    // Agora precisamos fazer uma segunda solicitação para a página do iframe
-    let resp = client
-        .get(&iframe_url)
-        .header("User-Agent", USER_AGENT)
-        .header("Referer", episode_url)
-        .send()
-        .await?;
+    println!("Encontrados {} servidores", servers.len());
+    Ok(servers)
+}
+
+// Extrai todos os servidores listados na página do episódio
+fn parse_servers(html: &str, episode_url: &str) -> Vec<StreamServer> {
+    let document = Html::parse_document(html);
     
-    if !resp.status().is_success() {
-        return Err(anyhow!("Falha ao carregar player: HTTP {}", resp.status()));
-    }
+    // Cada servidor é um <li class="vidcdn"><a data-video="//...">Nome<span>...</span></a></li>
+    let item_selector = Selector::parse("div.anime_muti_link ul li").unwrap();
+    let link_selector = Selector::parse("a[data-video]").unwrap();
     
-    let player_html = resp.text().await?;
-    
-    // Buscamos pelo link direto do vídeo no HTML ou JSON do player
-    // Note: Essa parte é instável e pode precisar de atualizações frequentes
-    // conforme o site muda sua estrutura
-    
-    // Vamos tentar diferentes padrões para encontrar a URL do vídeo
-    // 1. Procura pelo padrão "file":"URL_DO_VIDEO"
-    if let Some(pos) = player_html.find("\"file\":\"") {
-        let start = pos + 8; // 8 é o comprimento de "\"file\":\""
-        if let Some(end) = player_html[start..].find("\"") {
-            let video_url = &player_html[start..start + end];
-            // Decodifica sequências de escape JSON (\/)
-            let video_url = video_url.replace("\\/", "/");
-            println!("URL de streaming encontrada (padrão 1): {}", video_url);
-            return Ok(video_url);
-        }
+    let mut servers = Vec::new();
+    for item in document.select(&item_selector) {
+        let Some(link) = item.select(&link_selector).next() else {
+            continue;
+        };
+        let Some(video) = link.value().attr("data-video") else {
+            continue;
+        };
+        
+        // O nome é o primeiro texto do link, antes do <span>
+        let name = link
+            .text()
+            .next()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| item.value().attr("class").unwrap_or("desconhecido").to_string());
+        
+        let kind = match ServerKind::from_label(&name) {
+            ServerKind::Other => ServerKind::from_label(item.value().attr("class").unwrap_or("")),
+            kind => kind,
+        };
+        
+        servers.push(StreamServer {
+            kind,
+            name,
+            url: absolute_url(video),
+            referer: Some(episode_url.to_string()),
+        });
     }
     
-    // 2. Procura pelo padrão "src":"URL_DO_VIDEO"
-    if let Some(pos) = player_html.find("\"src\":\"") {
-        let start = pos + 7; // 7 é o comprimento de "\"src\":\""
-        if let Some(end) = player_html[start..].find("\"") {
-            let video_url = &player_html[start..start + end];
-            let video_url = video_url.replace("\\/", "/");
-            println!("URL de streaming encontrada (padrão 2): {}", video_url);
-            return Ok(video_url);
+    // Páginas antigas não têm a lista, apenas o iframe do player
+    if servers.is_empty() {
+        let iframe_selectors = [
+            Selector::parse("div.play-video iframe").unwrap(),
+            Selector::parse("iframe#player").unwrap(),
+            Selector::parse("iframe[src*='streaming']").unwrap(),
+            Selector::parse("iframe").unwrap(),
+        ];
+        
+        if let Some(src) = iframe_selectors
+            .iter()
+            .find_map(|selector| document.select(selector).next().and_then(|iframe| iframe.value().attr("src")))
+        {
+            servers.push(StreamServer {
+                kind: ServerKind::from_label(src),
+                name: "Player".to_string(),
+                url: absolute_url(src),
+                referer: Some(episode_url.to_string()),
+            });
         }
     }
     
-    // 3. Procura por URLs .mp4 ou .m3u8
-    let html_lowercase = player_html.to_lowercase();
-    for pattern in &[".mp4", ".m3u8"] {
-        if let Some(pos) = html_lowercase.find(pattern) {
-            // Retrocedendo até encontrar http ou https
-            let mut start = pos;
-            while start > 0 && !html_lowercase[start-7..start].contains("http") {
-                start -= 1;
-                if start < 7 { break; }
-            }
-            
-            if start >= 7 {
-                let proto_start = html_lowercase[start-7..start].find("http").unwrap() + (start-7);
-                let mut end = pos + pattern.len();
-                
-                // Avança até encontrar aspas, espaço ou >
-                while end < html_lowercase.len() && !"\"> ".contains(&html_lowercase[end..=end]) {
-                    end += 1;
-                }
-                
-                let video_url = &player_html[proto_start..end];
-                println!("URL de streaming encontrada (padrão 3): {}", video_url);
-                return Ok(video_url.to_string());
-            }
-        }
+    servers
+}
+
+// Se a URL não tiver schema (começando com //), adiciona https:
+fn absolute_url(src: &str) -> String {
+    if src.starts_with("//") {
+        format!("https:{}", src)
+    } else {
+        src.to_string()
     }
-    
-    // 4. Se não encontrarmos nada, vamos procurar qualquer URL de outro player
-    let document = Html::parse_document(&player_html);
-    let link_selector = Selector::parse("a[href*='streaming'], a[href*='watch'], iframe[src*='embed']").unwrap();
-    
-    if let Some(link) = document.select(&link_selector).next() {
-        if let Some(href) = link.value().attr("href").or_else(|| link.value().attr("src")) {
-            println!("Link alternativo encontrado: {}", href);
-            
-            // Se for URL relativa, adiciona o domínio base
-            let video_url = if href.starts_with("http") {
-                href.to_string()
-            } else if href.starts_with("//") {
-                format!("https:{}", href)
-            } else {
-                // Obtém domínio base da iframe_url
-                let base_url = Url::parse(&iframe_url)?;
-                let domain = format!("{}://{}", base_url.scheme(), base_url.host_str().unwrap_or(""));
-                format!("{}{}", domain, href)
-            };
-            
-            println!("Redirecionando para: {}", video_url);
-            return Ok(video_url);
-        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn episode_numbers_from_slugs() {
+        assert_eq!(slug_episode("frieren-episode-12"), "12");
+        assert_eq!(slug_episode("frieren-episode-12-5"), "12.5");
+        assert_eq!(slug_episode("frieren-episode-ova-1"), "ova-1");
+
+        let html = r#"<li><a href=" /frieren-episode-12-5"><div class="name"></div></a></li>
+                      <li><a href=" /frieren-episode-3"><div class="name">EP 3</div></a></li>"#;
+        let numbers: Vec<String> = parse_episode_list(html).iter().map(|e| e.number.to_string()).collect();
+        assert_eq!(numbers, ["12.5", "3"]);
     }
-    
-    // Se chegamos aqui, não conseguimos encontrar a URL do vídeo
-    println!("ERRO: Não foi possível encontrar a URL do vídeo no HTML:");
-    println!("Primeiros 200 caracteres: {}", &player_html[..200.min(player_html.len())]);
-    println!("Últimos 200 caracteres: {}", &player_html[player_html.len() - 200.min(player_html.len())..]);
-    
-    Err(anyhow!("Não foi possível extrair a URL do vídeo"))
-}
\ No newline at end of file
+}
@@ -1,10 +1,15 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, anyhow};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::models::EpisodeNumber;
+
+// A partir desta porcentagem o episódio é considerado assistido
+pub const FINISHED_PERCENT: f32 = 90.0;
+
 // Estrutura para armazenar o histórico de visualização
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct WatchHistory {
@@ -16,9 +21,23 @@ pub struct WatchHistory {
 pub struct HistoryEntry {
     pub anime_id: String,
     pub anime_title: String,
-    pub last_episode: String,
+    pub last_episode: EpisodeNumber,
     pub total_episodes: Option<i32>,
     pub timestamp: u64,
+    #[serde(default)]
+    pub provider: String,  // Provedor usado (vazio em históricos antigos)
+    #[serde(default)]
+    pub position: Option<f64>,    // Onde a reprodução parou, em segundos (episódio não terminado)
+    #[serde(default)]
+    pub completion: Option<f32>,  // Porcentagem assistida do último episódio
+}
+
+impl HistoryEntry {
+    // Indica se o último episódio foi assistido até o fim. Entradas sem
+    // porcentagem (históricos antigos, players sem IPC) contam como assistidas
+    pub fn is_finished(&self) -> bool {
+        self.completion.is_none_or(|c| c >= FINISHED_PERCENT)
+    }
 }
 
 impl WatchHistory {
@@ -78,7 +97,7 @@ impl WatchHistory {
         
         // Limita o histórico a 100 entradas, removendo as mais antigas
         if self.entries.len() > 100 {
-            self.entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            self.entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
             self.entries.truncate(100);
         }
         
@@ -91,6 +110,98 @@ impl WatchHistory {
             .max_by_key(|entry| entry.timestamp)
     }
     
+    // Posição para retomar um episódio que ficou pela metade
+    pub fn resume_position(&self, anime_id: &str, episode: &EpisodeNumber) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|e| e.anime_id == anime_id && &e.last_episode == episode)
+            .filter(|e| !e.is_finished())
+            .and_then(|e| e.position)
+    }
+    
+    // Descarta a posição salva do episódio, para ele recomeçar do início
+    pub fn forget_position(&mut self, anime_id: &str, episode: &EpisodeNumber) {
+        if let Some(entry) = self.entries
+            .iter_mut()
+            .find(|e| e.anime_id == anime_id && &e.last_episode == episode)
+        {
+            entry.position = None;
+        }
+    }
+    
+    // Encontra a entrada de um anime pelo título: primeiro a correspondência
+    // exata (sem diferenciar maiúsculas), depois uma única correspondência parcial
+    pub fn find_by_title(&self, title: &str) -> Result<usize> {
+        let wanted = title.to_lowercase();
+        
+        if let Some(idx) = self.entries.iter().position(|e| e.anime_title.to_lowercase() == wanted) {
+            return Ok(idx);
+        }
+        
+        let matches: Vec<usize> = self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.anime_title.to_lowercase().contains(&wanted))
+            .map(|(idx, _)| idx)
+            .collect();
+        
+        match matches.as_slice() {
+            [idx] => Ok(*idx),
+            [] => Err(anyhow!("Nenhum anime no histórico corresponde a \"{}\"", title)),
+            _ => {
+                let titles: Vec<&str> = matches.iter().map(|i| self.entries[*i].anime_title.as_str()).collect();
+                Err(anyhow!("Mais de um anime corresponde a \"{}\": {}", title, titles.join(", ")))
+            }
+        }
+    }
+    
+    // Remove a entrada de um anime pelo título
+    pub fn remove(&mut self, title: &str) -> Result<HistoryEntry> {
+        let idx = self.find_by_title(title)?;
+        let entry = self.entries.remove(idx);
+        self.save()?;
+        Ok(entry)
+    }
+    
+    // Marca um episódio como o último assistido de um anime
+    pub fn mark(&mut self, title: &str, episode: EpisodeNumber) -> Result<&HistoryEntry> {
+        let idx = self.find_by_title(title)?;
+        let entry = &mut self.entries[idx];
+        entry.last_episode = episode;
+        entry.position = None;
+        entry.completion = None;
+        entry.timestamp = get_current_timestamp()?;
+        self.save()?;
+        Ok(&self.entries[idx])
+    }
+    
+    // Exporta as entradas em JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.entries)
+            .context("Falha ao serializar histórico")
+    }
+    
+    // Exporta as entradas em CSV, com cabeçalho
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("anime_id,anime_title,last_episode,total_episodes,timestamp,provider,position,completion\n");
+        for e in &self.entries {
+            let fields = [
+                e.anime_id.clone(),
+                e.anime_title.clone(),
+                e.last_episode.to_string(),
+                e.total_episodes.map(|t| t.to_string()).unwrap_or_default(),
+                e.timestamp.to_string(),
+                e.provider.clone(),
+                e.position.map(|p| format!("{:.1}", p)).unwrap_or_default(),
+                e.completion.map(|c| format!("{:.1}", c)).unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+    
     // Limpa o histórico
     pub fn clear(&mut self) -> Result<()> {
         self.entries.clear();
@@ -98,6 +209,15 @@ impl WatchHistory {
     }
 }
 
+// Coloca o campo entre aspas quando necessário (vírgulas, aspas ou quebras de linha)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 // Obtém o caminho para o arquivo de histórico
 fn get_history_path() -> Result<PathBuf> {
     let mut path = dirs::config_dir()
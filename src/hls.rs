@@ -0,0 +1,323 @@
+use anyhow::{Result, anyhow};
+use reqwest::Client;
+use std::collections::{BTreeMap, HashMap};
+use url::Url;
+
+use crate::extractor::USER_AGENT;
+use crate::models::{Quality, StreamSource};
+
+// Variante de uma playlist HLS master (uma por qualidade)
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub url: String,                    // URL absoluta da playlist de mídia
+    pub bandwidth: u64,                 // Taxa de bits informada (BANDWIDTH)
+    pub resolution: Option<(u32, u32)>, // Largura x altura (RESOLUTION)
+    pub codecs: Option<String>,         // Codecs (CODECS)
+}
+
+impl Variant {
+    pub fn height(&self) -> Option<u32> {
+        self.resolution.map(|(_, h)| h)
+    }
+}
+
+// Indica se a URL aponta para uma playlist HLS
+pub fn is_hls(url: &str) -> bool {
+    url.split('?').next().unwrap_or(url).to_lowercase().ends_with(".m3u8")
+}
+
+// Indica se o conteúdo é uma playlist master (lista de variantes)
+pub fn is_master(content: &str) -> bool {
+    content.contains("#EXT-X-STREAM-INF")
+}
+
+// Baixa uma playlist enviando os cabeçalhos exigidos pela fonte
+pub async fn fetch_playlist(url: &str, headers: &BTreeMap<String, String>) -> Result<String> {
+    let mut request = Client::new()
+        .get(url)
+        .header("User-Agent", USER_AGENT);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let resp = request.send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Falha ao carregar playlist: HTTP {}", resp.status()));
+    }
+
+    let content = resp.text().await?;
+    if !content.trim_start().starts_with("#EXTM3U") {
+        return Err(anyhow!("Resposta não é uma playlist HLS: {}", url));
+    }
+    Ok(content)
+}
+
+// Lê as variantes de uma playlist master:
+//   #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,CODECS="avc1.640028,mp4a.40.2"
+//   1080p/index.m3u8
+pub fn parse_master(content: &str, base: &Url) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut lines = content.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") else {
+            continue;
+        };
+        let attrs = parse_attributes(attrs);
+
+        // A URL é a próxima linha que não é comentário nem vazia
+        let Some(uri) = lines.by_ref().find(|l| !l.is_empty() && !l.starts_with('#')) else {
+            break;
+        };
+        let Ok(url) = base.join(uri) else {
+            continue;
+        };
+
+        let resolution = attrs.get("RESOLUTION").and_then(|r| {
+            let (w, h) = r.split_once('x')?;
+            Some((w.parse().ok()?, h.parse().ok()?))
+        });
+
+        variants.push(Variant {
+            url: url.to_string(),
+            bandwidth: attrs.get("BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+            resolution,
+            codecs: attrs.get("CODECS").cloned(),
+        });
+    }
+
+    variants
+}
+
+// Segmento de uma playlist de mídia
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub url: String,              // URL absoluta do segmento
+    pub sequence: u64,            // Número de sequência (EXT-X-MEDIA-SEQUENCE + posição)
+    pub duration: f64,            // Duração em segundos (EXTINF)
+    pub key: Option<SegmentKey>,  // Chave AES-128, se o segmento for cifrado
+}
+
+// Chave de um #EXT-X-KEY:METHOD=AES-128
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub url: String,            // URL absoluta da chave (16 bytes)
+    pub iv: Option<[u8; 16]>,   // IV explícito; sem ele, o IV é o número de sequência
+}
+
+impl SegmentKey {
+    // IV usado para decifrar o segmento de número `sequence`
+    pub fn iv_for(&self, sequence: u64) -> [u8; 16] {
+        self.iv.unwrap_or_else(|| (sequence as u128).to_be_bytes())
+    }
+}
+
+// Lê os segmentos de uma playlist de mídia:
+//   #EXT-X-MEDIA-SEQUENCE:100
+//   #EXT-X-KEY:METHOD=AES-128,URI="key.bin",IV=0x0000000000000000000000000000000A
+//   #EXTINF:10.0,
+//   seg100.ts
+pub fn parse_media(content: &str, base: &Url) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut sequence = 0;
+    let mut key: Option<SegmentKey> = None;
+    let mut duration = 0.0;
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(value) = line.strip_prefix("#EXTINF:") {
+            duration = value.split(',').next().and_then(|d| d.trim().parse().ok()).unwrap_or(0.0);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            sequence = value.trim().parse().unwrap_or(0);
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+            let attrs = parse_attributes(attrs);
+            key = match attrs.get("METHOD").map(String::as_str) {
+                Some("NONE") | None => None,
+                Some("AES-128") => {
+                    let uri = attrs
+                        .get("URI")
+                        .ok_or_else(|| anyhow!("#EXT-X-KEY sem URI"))?;
+                    let iv = match attrs.get("IV") {
+                        Some(iv) => Some(parse_iv(iv)?),
+                        None => None,
+                    };
+                    Some(SegmentKey { url: base.join(uri)?.to_string(), iv })
+                }
+                Some(method) => return Err(anyhow!("Criptografia HLS não suportada: {}", method)),
+            };
+        } else if line.starts_with("#EXT-X-MAP") || line.starts_with("#EXT-X-BYTERANGE") {
+            return Err(anyhow!("Playlists com {} não são suportadas", line.split(':').next().unwrap_or(line)));
+        } else if !line.starts_with('#') {
+            segments.push(Segment {
+                url: base.join(line)?.to_string(),
+                sequence,
+                duration,
+                key: key.clone(),
+            });
+            sequence += 1;
+            duration = 0.0;
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(anyhow!("A playlist não tem segmentos"));
+    }
+    Ok(segments)
+}
+
+// IV em hexadecimal ("0x" + 32 dígitos)
+fn parse_iv(value: &str) -> Result<[u8; 16]> {
+    let hex = value.trim_start_matches("0x").trim_start_matches("0X");
+    let number = u128::from_str_radix(hex, 16).map_err(|_| anyhow!("IV inválido: {}", value))?;
+    Ok(number.to_be_bytes())
+}
+
+// Lê uma lista de atributos HLS (CHAVE=valor,CHAVE="valor, com vírgula")
+pub fn parse_attributes(list: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = list.trim();
+
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.split_once(',') {
+                Some((value, remaining)) => (value, remaining),
+                None => (after, ""),
+            }
+        };
+
+        attrs.insert(key.trim().to_uppercase(), value.to_string());
+        rest = remaining.trim_start_matches(',').trim();
+    }
+
+    attrs
+}
+
+// Escolhe a variante que melhor atende à qualidade pedida
+pub fn select_variant(variants: &[Variant], quality: Quality) -> Option<&Variant> {
+    if variants.iter().any(|v| v.resolution.is_some()) {
+        return quality
+            .select(variants, Variant::height)
+            .map(|idx| &variants[idx]);
+    }
+
+    // Sem resolução informada, a taxa de bits é a única referência
+    match quality {
+        Quality::Worst => variants.iter().min_by_key(|v| v.bandwidth),
+        _ => variants.iter().max_by_key(|v| v.bandwidth),
+    }
+}
+
+// Escolhe a fonte e, se for uma playlist master, a variante de acordo com a
+// qualidade pedida. Os cabeçalhos da fonte são mantidos na variante escolhida
+pub async fn select_stream(sources: Vec<StreamSource>, quality: Quality) -> Result<StreamSource> {
+    let idx = quality
+        .select(&sources, |s| s.quality.as_deref().and_then(Quality::label_height))
+        .ok_or_else(|| anyhow!("Nenhuma fonte de streaming disponível"))?;
+    let mut source = sources.into_iter().nth(idx).unwrap();
+
+    if !is_hls(&source.url) {
+        return Ok(source);
+    }
+
+    let content = match fetch_playlist(&source.url, &source.headers).await {
+        Ok(content) => content,
+        Err(e) => {
+            // Deixa o player tentar a playlist original
+            println!("Não foi possível analisar a playlist HLS: {}", e);
+            return Ok(source);
+        }
+    };
+
+    if !is_master(&content) {
+        return Ok(source);
+    }
+
+    let base = Url::parse(&source.url)?;
+    let variants = parse_master(&content, &base);
+    if let Some(variant) = select_variant(&variants, quality) {
+        let label = match variant.height() {
+            Some(h) => format!("{}p", h),
+            None => format!("{} kbps", variant.bandwidth / 1000),
+        };
+        println!(
+            "Qualidade escolhida: {} [{}] ({} variantes disponíveis)",
+            label,
+            variant.codecs.as_deref().unwrap_or("codecs desconhecidos"),
+            variants.len()
+        );
+        source.url = variant.url.clone();
+        source.quality = Some(label);
+    }
+
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(bandwidth: u64, resolution: Option<(u32, u32)>) -> Variant {
+        Variant { url: format!("https://cdn/{}.m3u8", bandwidth), bandwidth, resolution, codecs: None }
+    }
+
+    #[test]
+    fn parses_master_playlist() {
+        let content = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,CODECS=\"avc1.640028,mp4a.40.2\"\n\
+            1080p/index.m3u8\n\
+            \n\
+            #EXT-X-STREAM-INF:CODECS=\"avc1.4d401f,mp4a.40.2\",RESOLUTION=1280x720,BANDWIDTH=2800000\n\
+            # comentário entre a tag e a URL\n\
+            ../720/index.m3u8?token=abc\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000\n\
+            https://other.cdn/360.m3u8\n";
+        let base = Url::parse("https://cdn.example/hls/ep1/master.m3u8?token=abc").unwrap();
+        let variants = parse_master(content, &base);
+
+        assert_eq!(variants.len(), 3);
+        assert_eq!(variants[0].url, "https://cdn.example/hls/ep1/1080p/index.m3u8");
+        assert_eq!(variants[0].resolution, Some((1920, 1080)));
+        assert_eq!(variants[0].bandwidth, 5_000_000);
+        assert_eq!(variants[0].codecs.as_deref(), Some("avc1.640028,mp4a.40.2"));
+        assert_eq!(variants[1].url, "https://cdn.example/hls/720/index.m3u8?token=abc");
+        assert_eq!(variants[1].height(), Some(720));
+        assert_eq!(variants[1].bandwidth, 2_800_000);
+        assert_eq!(variants[2].url, "https://other.cdn/360.m3u8");
+        assert_eq!(variants[2].resolution, None);
+    }
+
+    #[test]
+    fn selects_nearest_variant() {
+        let variants = [
+            variant(800, Some((640, 360))),
+            variant(2800, Some((1280, 720))),
+            variant(5000, Some((1920, 1080))),
+        ];
+        let height = |quality| select_variant(&variants, quality).and_then(Variant::height);
+        assert_eq!(height(Quality::Best), Some(1080));
+        assert_eq!(height(Quality::Worst), Some(360));
+        assert_eq!(height(Quality::Height(720)), Some(720));
+        assert_eq!(height(Quality::Height(480)), Some(360));
+        // Empate (540 fica a 180 de 360 e de 720): a maior
+        assert_eq!(height(Quality::Height(540)), Some(720));
+        assert_eq!(height(Quality::Height(2160)), Some(1080));
+    }
+
+    #[test]
+    fn selects_by_bandwidth_without_resolution() {
+        let variants = [variant(2800, None), variant(5000, None), variant(800, None)];
+        let bandwidth = |quality| select_variant(&variants, quality).map(|v| v.bandwidth);
+        assert_eq!(bandwidth(Quality::Best), Some(5000));
+        assert_eq!(bandwidth(Quality::Worst), Some(800));
+        assert_eq!(bandwidth(Quality::Height(360)), Some(5000));
+        assert!(select_variant(&[], Quality::Best).is_none());
+    }
+}
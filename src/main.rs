@@ -1,87 +1,1075 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 
+mod api;
+mod config;
+mod discovery;
+mod download;
+mod extractor;
+mod filename;
 mod gogoanime;
+mod history;
+mod hls;
+mod models;
+mod mpv;
 mod player;
+mod provider;
+mod queue;
+mod skip;
 mod ui;
+mod utils;
 
-use gogoanime::{search_anime, get_episodes, get_stream_url, AnimeItem, EpisodeItem};
-use ui::{prompt_input, select_from_list};
-use player::play_with_mpv;
+use download::DownloadOptions;
+use queue::{DownloadJob, DownloadQueue, JobStatus, SourceQuery};
+use history::{get_current_timestamp, HistoryEntry, WatchHistory};
+use hls::select_stream;
+use models::{pair_variants, sort_subtitles, AnimeItem, Audio, EpisodeItem, EpisodeNumber, EpisodeSelection, Quality, ServerKind, StreamServer, StreamSource};
+use provider::{get_provider, resolve_sources, AnimeProvider};
+use ui::{confirm, prompt_input, select_from_list};
+use utils::{extract_number, format_duration, format_timestamp};
+use config::Config;
+use player::{play_video, EpisodeFinished, MediaOptions, MpvPlayer, PlaybackProgress, PlayerKind, PlayerOptions};
+use skip::{AniSkip, SkipInterval, SkipMode};
 
 #[derive(Parser, Debug)]
 #[command(name = "AniRust", about = "Assista animes via CLI!")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Termo de busca (nome do anime)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     query: Option<String>,
 
-    /// Número do episódio (opcional)
-    #[arg(short, long)]
-    episode: Option<usize>,
+    /// Episódios (opcional): 12, 12.5, OVA, 1-12, 3,5,7-9, 10- ou -1 (último)
+    #[arg(short, long, allow_hyphen_values = true, global = true)]
+    episode: Option<EpisodeSelection>,
+
+    /// Continua o último anime do histórico no próximo episódio
+    #[arg(short, long = "continue")]
+    continue_watching: bool,
+
+    /// Fonte dos animes (gogoanime, consumet)
+    #[arg(short, long, default_value = provider::DEFAULT_PROVIDER, global = true)]
+    provider: String,
+
+    /// Prefere a versão dublada do anime
+    #[arg(long, conflicts_with = "sub", global = true)]
+    dub: bool,
+
+    /// Prefere a versão legendada do anime (padrão)
+    #[arg(long, global = true)]
+    sub: bool,
+
+    /// Qualidade do vídeo: best, worst ou a altura (1080, 720, 480...)
+    #[arg(long, default_value = "best", global = true)]
+    quality: Quality,
+
+    /// Ordem de tentativa dos servidores de vídeo (ex: streamwish,vidstreaming)
+    #[arg(long, value_enum, value_delimiter = ',', global = true)]
+    servers: Vec<ServerKind>,
+
+    /// Escolhe manualmente o servidor de vídeo
+    #[arg(long, global = true)]
+    select_server: bool,
+
+    /// Player usado para reproduzir (padrão: mpv ou o do config.json)
+    #[arg(long, value_enum)]
+    player: Option<PlayerKind>,
+
+    /// Comando do player custom, com {url}, {title} e {referer} (ex: "celluloid {url}")
+    #[arg(long)]
+    player_command: Option<String>,
+
+    /// Abre o player em janela, sem tela cheia
+    #[arg(long)]
+    no_fullscreen: bool,
+
+    /// Não espera o player fechar (o progresso não é salvo)
+    #[arg(long)]
+    detach: bool,
+
+    /// Sai do programa quando o player fechar, sem mostrar o menu
+    #[arg(long)]
+    exit_after_play: bool,
+
+    /// Idiomas de legenda preferidos, em ordem (ex: pt,en)
+    #[arg(long, value_delimiter = ',', global = true)]
+    sub_lang: Vec<String>,
+
+    /// Toca os episódios seguintes em sequência, com contagem regressiva
+    #[arg(long)]
+    nextep_countdown: bool,
+
+    /// Pula aberturas, encerramentos e recapitulações (tempos do AniSkip)
+    #[arg(long)]
+    skip_intro: bool,
+
+    /// Como pular: automaticamente ou ao apertar TAB
+    #[arg(long, value_enum, default_value_t = SkipMode::Auto)]
+    skip_mode: SkipMode,
+
+    /// Título (ou ID do MyAnimeList) usado para buscar os tempos de pulo
+    #[arg(long)]
+    skip_title: Option<String>,
+
+    /// URL base da API compatível com o AniSkip
+    #[arg(long, default_value = skip::DEFAULT_ANISKIP_URL)]
+    aniskip_url: String,
+
+    /// URL base da API compatível com o Jikan, usada para achar o ID do
+    /// MyAnimeList pelo título
+    #[arg(long, default_value = skip::DEFAULT_JIKAN_URL)]
+    jikan_url: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Baixa episódios (use -q e -e para escolher o anime e os episódios)
+    Download {
+        #[command(subcommand)]
+        action: Option<DownloadCommand>,
+
+        /// Pasta onde os episódios são salvos
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Modelo do caminho de cada episódio, dentro da pasta de destino.
+        /// Campos: {title}, {series}, {season}, {episode}, {episode_title},
+        /// {quality}, {audio}, {provider}, {ext}; números aceitam {episode:02}
+        #[arg(short, long, default_value = filename::DEFAULT_TEMPLATE)]
+        template: String,
+
+        /// Temporada usada no nome dos arquivos (padrão: detectada pelo título)
+        #[arg(long)]
+        season: Option<u32>,
+
+        /// Novas tentativas quando a conexão cai ou o arquivo vem incompleto
+        #[arg(long, default_value_t = download::DEFAULT_RETRIES, global = true)]
+        retries: u32,
+
+        /// Segmentos HLS baixados ao mesmo tempo
+        #[arg(long, default_value_t = download::DEFAULT_CONCURRENCY, global = true)]
+        concurrency: usize,
+
+        /// Mantém o vídeo HLS em .ts, sem converter para .mp4 com o ffmpeg
+        #[arg(long, global = true)]
+        no_remux: bool,
+
+        /// Episódios baixados ao mesmo tempo
+        #[arg(short, long, default_value_t = 2, global = true)]
+        jobs: usize,
+
+        /// Execuções de cada episódio antes de desistir
+        #[arg(long, default_value_t = queue::DEFAULT_MAX_ATTEMPTS, global = true)]
+        attempts: u32,
+
+        /// Só adiciona os episódios à fila, sem começar a baixar
+        #[arg(long)]
+        no_start: bool,
+    },
+
+    /// Gerencia o histórico de visualização
+    History {
+        #[command(subcommand)]
+        action: HistoryCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DownloadCommand {
+    /// Mostra a fila de downloads
+    List,
+
+    /// Continua os downloads pendentes da fila
+    Run,
+
+    /// Coloca de volta na fila os downloads que falharam
+    Retry,
+
+    /// Remove da fila os downloads concluídos e falhos
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Lista os animes do histórico
+    List {
+        /// Ordenação da lista
+        #[arg(long, value_enum, default_value_t = HistorySort::Recent)]
+        sort: HistorySort,
+    },
+
+    /// Remove um anime do histórico
+    Remove {
+        /// Título do anime (ou parte dele)
+        title: String,
+    },
+
+    /// Marca o último episódio assistido de um anime
+    Mark {
+        /// Título do anime (ou parte dele)
+        title: String,
+        /// Número do episódio: 12, 12.5, OVA...
+        episode: EpisodeNumber,
+    },
+
+    /// Apaga todo o histórico
+    Clear {
+        /// Não pede confirmação
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Exporta o histórico
+    Export {
+        /// Formato do arquivo
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        /// Arquivo de saída (padrão: saída padrão)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum HistorySort {
+    /// Assistidos mais recentemente primeiro
+    Recent,
+    /// Ordem alfabética
+    Title,
+    /// Maior progresso (episódio atual / total) primeiro
+    Progress,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    Json,
+    Csv,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    match &args.command {
+        Some(Command::History { action }) => return run_history_command(action),
+        Some(Command::Download { action, output, template, season, retries, concurrency, no_remux, jobs, attempts, no_start }) => {
+            let options = DownloadOptions {
+                retries: *retries,
+                concurrency: *concurrency,
+                remux: !no_remux,
+                ..Default::default()
+            };
+            return match action {
+                Some(action) => run_download_command(action, &options, *jobs, *attempts).await,
+                None => {
+                    enqueue_downloads(&args, output, template, *season).await?;
+                    if *no_start {
+                        return Ok(());
+                    }
+                    queue::run_queue(&options, *jobs, *attempts).await
+                }
+            };
+        }
+        None => {}
+    }
+
     println!("AniRust - Assistente de Anime");
-    let query = args.query.unwrap_or_else(|| prompt_input("Digite o nome do anime:").unwrap());
+
+    let mut history = WatchHistory::load().unwrap_or_else(|e| {
+        eprintln!("Erro ao carregar histórico: {}", e);
+        WatchHistory::default()
+    });
+
+    let started = if args.continue_watching {
+        start_from_history(&history).await?
+    } else {
+        start_from_search(&args).await?
+    };
+    let Some((mut session, mut queue)) = started else {
+        return Ok(());
+    };
+
+    let mut playback = Playback::new(&args, &session).await;
+    let mut pick_server_once = false;
+
+    loop {
+        // "Trocar servidor" vale só para a próxima reprodução
+        let pick_server = playback.pick_server || std::mem::take(&mut pick_server_once);
+
+        let mut chosen = queue[0].clone();
+        let options = &playback.options;
+        if (queue.len() > 1 || options.nextep_countdown) && options.player == PlayerKind::Mpv && !options.detach {
+            // Playlist: cada episódio é salvo no histórico ao terminar
+            match play_playlist(&session, &playback, &queue, &mut history, pick_server).await {
+                Ok(last) => chosen = last,
+                Err(e) => eprintln!("{}", e),
+            }
+        } else if queue.len() > 1 || options.nextep_countdown {
+            match play_sequence(&session, &playback, &queue, &mut history, pick_server).await {
+                Ok(last) => chosen = last,
+                Err(e) => eprintln!("{}", e),
+            }
+        } else {
+            let media = playback.media(&history, &session, &chosen).await;
+            match play_episode(&session, &playback, &chosen, &media, pick_server).await {
+                Ok(progress) => {
+                    if let Err(e) = record_history(&mut history, &session, &chosen, &progress) {
+                        eprintln!("Erro ao salvar histórico: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        if playback.options.exit_after_play {
+            return Ok(());
+        }
+
+        // Menu após o episódio, sem buscar o anime de novo; repete até o
+        // usuário escolher o que assistir
+        let next = loop {
+            match episode_menu(&session, &chosen)? {
+                MenuAction::Play(episode) => break episode,
+                MenuAction::Replay => {
+                    // Recomeça do início, mesmo se o episódio ficou pela metade
+                    history.forget_position(&session.anime_id, &chosen.number);
+                    break chosen.clone();
+                }
+                MenuAction::Select => break select_episode(&session)?,
+                MenuAction::ChangeServer => {
+                    pick_server_once = true;
+                    break chosen.clone();
+                }
+                MenuAction::ChangeQuality => {
+                    playback.quality = select_quality(playback.quality)?;
+                    break chosen.clone();
+                }
+                MenuAction::SwitchAudio(audio) => match session.switch_audio(audio, &chosen.number).await {
+                    Ok(episode) => break episode,
+                    Err(e) => eprintln!("{}", e),
+                },
+                MenuAction::Quit => return Ok(()),
+            }
+        };
+        queue = vec![next];
+    }
+}
+
+// Ações do menu exibido após cada episódio
+enum MenuAction {
+    Play(EpisodeItem), // Próximo ou anterior
+    Replay,
+    Select,
+    ChangeServer,
+    ChangeQuality,
+    SwitchAudio(Audio),
+    Quit,
+}
+
+// Mostra as opções disponíveis para o episódio que acabou de tocar
+fn episode_menu(session: &Session, episode: &EpisodeItem) -> Result<MenuAction> {
+    let mut actions = Vec::new();
+
+    if let Some(next) = session.next_episode(&episode.number) {
+        actions.push((format!("Próximo episódio ({})", next.number), MenuAction::Play(next)));
+    }
+    if let Some(previous) = session.previous_episode(&episode.number) {
+        actions.push((format!("Episódio anterior ({})", previous.number), MenuAction::Play(previous)));
+    }
+    actions.push((format!("Repetir episódio {}", episode.number), MenuAction::Replay));
+    actions.push(("Selecionar episódio".to_string(), MenuAction::Select));
+    actions.push(("Trocar servidor".to_string(), MenuAction::ChangeServer));
+    actions.push(("Trocar qualidade".to_string(), MenuAction::ChangeQuality));
+
+    let other = session.audio.other();
+    if session.anime.variant_id(other).is_some() {
+        let label = format!("Trocar para {} (episódio {})", other.to_string().to_lowercase(), episode.number);
+        actions.push((label, MenuAction::SwitchAudio(other)));
+    }
+    actions.push(("Sair".to_string(), MenuAction::Quit));
+
+    let labels: Vec<String> = actions.iter().map(|(label, _)| label.clone()).collect();
+    let prompt = format!("{} — episódio {} ({})", session.anime.title, episode.number, session.audio);
+    let idx = select_from_list(&labels, &prompt)?;
+    Ok(actions.swap_remove(idx).1)
+}
+
+// Lista os episódios da sessão para o usuário escolher
+fn select_episode(session: &Session) -> Result<EpisodeItem> {
+    let labels: Vec<String> = session.episodes
+        .iter()
+        .map(|e| match &e.title {
+            Some(title) => format!("Ep. {} - {}", e.number, title),
+            None => format!("Episódio {}", e.number),
+        })
+        .collect();
+
+    let idx = select_from_list(&labels, "Selecione um episódio:")?;
+    Ok(session.episodes[idx].clone())
+}
+
+// Pergunta a nova qualidade, marcando a atual
+fn select_quality(current: Quality) -> Result<Quality> {
+    let choices = [
+        Quality::Best,
+        Quality::Worst,
+        Quality::Height(1080),
+        Quality::Height(720),
+        Quality::Height(480),
+        Quality::Height(360),
+    ];
+    let labels: Vec<String> = choices
+        .iter()
+        .map(|q| if *q == current { format!("{} (atual)", q) } else { q.to_string() })
+        .collect();
+
+    let idx = select_from_list(&labels, "Selecione a qualidade:")?;
+    Ok(choices[idx])
+}
+
+// Anime que está sendo assistido, com o provedor e a versão (legendada/dublada)
+struct Session {
+    provider: Box<dyn AnimeProvider>,
+    anime: AnimeItem,
+    audio: Audio,
+    anime_id: String,            // ID da versão escolhida
+    episodes: Vec<EpisodeItem>,  // Mais recentes primeiro
+}
+
+impl Session {
+    async fn new(provider: Box<dyn AnimeProvider>, anime: AnimeItem, audio: Audio, anime_id: String) -> Result<Self> {
+        println!("Buscando episódios...");
+        let mut episodes = provider.episodes(&anime_id).await?;
+
+        // Mais recentes primeiro, independente da ordem retornada pelo provedor
+        episodes.sort_by(|a, b| b.number.cmp(&a.number));
+
+        Ok(Self { provider, anime, audio, anime_id, episodes })
+    }
+
+    // Troca para a outra versão do anime e retorna o mesmo episódio nela
+    async fn switch_audio(&mut self, audio: Audio, number: &EpisodeNumber) -> Result<EpisodeItem> {
+        let variant_id = self
+            .anime
+            .variant_id(audio)
+            .ok_or_else(|| anyhow!("Versão {} não disponível", audio))?
+            .to_string();
+
+        let mut episodes = self.provider.episodes(&variant_id).await?;
+        episodes.sort_by(|a, b| b.number.cmp(&a.number));
+
+        let episode = episodes
+            .iter()
+            .find(|e| &e.number == number)
+            .cloned()
+            .ok_or_else(|| anyhow!("Episódio {} não disponível nesta versão", number))?;
+
+        self.audio = audio;
+        self.anime_id = variant_id;
+        self.episodes = episodes;
+        Ok(episode)
+    }
+
+    // Episódio seguinte: o menor número maior que o informado
+    fn next_episode(&self, number: &EpisodeNumber) -> Option<EpisodeItem> {
+        self.episodes
+            .iter()
+            .filter(|e| &e.number > number)
+            .min_by(|a, b| a.number.cmp(&b.number))
+            .cloned()
+    }
+
+    // Episódio anterior: o maior número menor que o informado
+    fn previous_episode(&self, number: &EpisodeNumber) -> Option<EpisodeItem> {
+        self.episodes
+            .iter()
+            .filter(|e| &e.number < number)
+            .max_by(|a, b| a.number.cmp(&b.number))
+            .cloned()
+    }
+}
+
+// Fluxo normal: busca o anime, escolhe a versão e o episódio
+async fn start_from_search(args: &Args) -> Result<Option<(Session, Vec<EpisodeItem>)>> {
+    let provider = get_provider(&args.provider)?;
+    let query = match &args.query {
+        Some(query) => query.clone(),
+        None => prompt_input("Digite o nome do anime:")?,
+    };
 
     println!("Buscando animes...");
-    let animes: Vec<AnimeItem> = match search_anime(&query).await {
+    let animes: Vec<AnimeItem> = match provider.search(&query).await {
         Ok(list) => list,
         Err(e) => {
             eprintln!("Erro ao buscar animes: {}", e);
-            return Ok(());
+            return Ok(None);
         }
     };
 
-    let titles: Vec<String> = animes.iter().map(|a| a.title.clone()).collect();
+    let titles: Vec<String> = animes
+        .iter()
+        .map(|a| match (a.variant_id(Audio::Sub), a.variant_id(Audio::Dub)) {
+            (Some(_), Some(_)) => format!("{} [Leg/Dub]", a.title),
+            (None, Some(_)) => format!("{} [Dub]", a.title),
+            _ => a.title.clone(),
+        })
+        .collect();
     let idx = select_from_list(&titles, "Selecione um anime:")?;
-    let selected = &animes[idx];
+    let selected = animes[idx].clone();
     println!("Você escolheu: {}", selected.title);
 
-    println!("Buscando episódios...");
-    let episodes: Vec<EpisodeItem> = match get_episodes(&selected.id).await {
-        Ok(list) => list,
+    let preferred = if args.dub { Audio::Dub } else { Audio::Sub };
+    let (audio, anime_id) = selected.preferred_variant(preferred);
+    let anime_id = anime_id.to_string();
+    if audio != preferred {
+        println!("Versão {} não disponível, usando {}", preferred, audio);
+    }
+
+    let session = match Session::new(provider, selected, audio, anime_id).await {
+        Ok(session) => session,
         Err(e) => {
             eprintln!("Erro ao obter episódios: {}", e);
-            return Ok(());
+            return Ok(None);
         }
     };
 
-    let eps_labels: Vec<String> = episodes
-        .iter()
-        .map(|e| {
-            let ep_num = e.number.clone();
-            match &e.title {
-                Some(title) => format!("Ep. {} - {}", ep_num, title),
-                _none => format!("Episódio {}", ep_num),
+    let selected = match &args.episode {
+        Some(selection) => {
+            let episodes = selection.select(&session.episodes);
+            if episodes.is_empty() {
+                println!("Nenhum episódio corresponde a {}", selection);
+            } else if episodes.len() > 1 {
+                println!("{} episódios selecionados ({})", episodes.len(), selection);
             }
-        })
-        .collect();
+            episodes
+        }
+        None => Vec::new(),
+    };
+
+    let queue = if selected.is_empty() { vec![select_episode(&session)?] } else { selected };
+    Ok(Some((session, queue)))
+}
+
+// Modo --continue: reabre o último anime assistido no próximo episódio
+async fn start_from_history(history: &WatchHistory) -> Result<Option<(Session, Vec<EpisodeItem>)>> {
+    let Some(entry) = history.get_latest_entry() else {
+        println!("Histórico vazio, nada para continuar");
+        return Ok(None);
+    };
+
+    let provider_name = if entry.provider.is_empty() { provider::DEFAULT_PROVIDER } else { entry.provider.as_str() };
+    let provider = get_provider(provider_name)?;
+    println!("Continuando {} (último episódio: {})", entry.anime_title, entry.last_episode);
+
+    // Reconstrói o item do anime a partir do histórico; a versão é deduzida do ID
+    let anime = pair_variants(vec![AnimeItem {
+        id: entry.anime_id.clone(),
+        title: entry.anime_title.clone(),
+        image: String::new(),
+        variants: Vec::new(),
+    }])
+    .remove(0);
+    let (audio, _) = anime.preferred_variant(Audio::Sub);
+
+    let session = Session::new(provider, anime, audio, entry.anime_id.clone()).await?;
+
+    // Um episódio que ficou pela metade é reaberto; senão, o próximo é o
+    // menor número maior que o último assistido
+    let unfinished = (!entry.is_finished())
+        .then(|| session.episodes.iter().find(|e| e.number == entry.last_episode).cloned())
+        .flatten();
+    let next = unfinished.or_else(|| session.next_episode(&entry.last_episode));
+
+    match next {
+        Some(episode) => Ok(Some((session, vec![episode]))),
+        None => {
+            println!("Você já assistiu todos os episódios disponíveis de {}", entry.anime_title);
+            Ok(None)
+        }
+    }
+}
+
+// Registra o episódio assistido no histórico. A posição só é guardada se o
+// episódio não foi assistido até o fim
+fn record_history(
+    history: &mut WatchHistory,
+    session: &Session,
+    episode: &EpisodeItem,
+    progress: &PlaybackProgress,
+) -> Result<()> {
+    let completion = progress.completion();
+    let finished = completion.is_none_or(|c| c >= history::FINISHED_PERCENT);
+
+    history.update_entry(HistoryEntry {
+        anime_id: session.anime_id.clone(),
+        anime_title: session.anime.title.clone(),
+        last_episode: episode.number.clone(),
+        total_episodes: Some(session.episodes.len() as i32),
+        timestamp: get_current_timestamp()?,
+        provider: session.provider.name().to_string(),
+        position: if finished { None } else { progress.position },
+        completion,
+    })
+}
+
+// Executa os subcomandos de `anirust history`
+fn run_history_command(action: &HistoryCommand) -> Result<()> {
+    let mut history = WatchHistory::load()?;
+
+    match action {
+        HistoryCommand::List { sort } => {
+            if history.entries.is_empty() {
+                println!("Histórico vazio");
+                return Ok(());
+            }
+
+            let now = get_current_timestamp()?;
+            let mut entries: Vec<&HistoryEntry> = history.entries.iter().collect();
+            match sort {
+                HistorySort::Recent => entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp)),
+                HistorySort::Title => entries.sort_by_key(|e| e.anime_title.to_lowercase()),
+                HistorySort::Progress => entries.sort_by(|a, b| progress(b).total_cmp(&progress(a))),
+            }
+
+            for (i, e) in entries.iter().enumerate() {
+                let total = e.total_episodes.map(|t| format!("/{}", t)).unwrap_or_default();
+                let provider = if e.provider.is_empty() { String::new() } else { format!(" ({})", e.provider) };
+                let stopped = match e.position {
+                    Some(pos) if !e.is_finished() => format!(" (parou em {})", format_timestamp(pos)),
+                    _ => String::new(),
+                };
+                println!(
+                    "{:>3}. {} — episódio {}{}{} — há {}{}",
+                    i + 1,
+                    e.anime_title,
+                    e.last_episode,
+                    total,
+                    stopped,
+                    format_duration(now.saturating_sub(e.timestamp)),
+                    provider
+                );
+            }
+        }
+        HistoryCommand::Remove { title } => {
+            let entry = history.remove(title)?;
+            println!("Removido do histórico: {}", entry.anime_title);
+        }
+        HistoryCommand::Mark { title, episode } => {
+            let entry = history.mark(title, episode.clone())?;
+            println!("{} marcado no episódio {}", entry.anime_title, entry.last_episode);
+        }
+        HistoryCommand::Clear { yes } => {
+            if !*yes && !confirm(&format!("Apagar {} entradas do histórico?", history.entries.len()))? {
+                return Ok(());
+            }
+            history.clear()?;
+            println!("Histórico apagado");
+        }
+        HistoryCommand::Export { format, output } => {
+            let content = match format {
+                ExportFormat::Json => history.to_json()? + "\n",
+                ExportFormat::Csv => history.to_csv(),
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(path, content)?;
+                    println!("Histórico exportado para {}", path.display());
+                }
+                None => print!("{}", content),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Fração assistida do anime (0 quando o total é desconhecido)
+fn progress(entry: &HistoryEntry) -> f32 {
+    let watched = extract_number(&entry.last_episode.to_string()).unwrap_or(0.0);
+    match entry.total_episodes {
+        Some(total) if total > 0 => watched / total as f32,
+        _ => 0.0,
+    }
+}
+
+// Ajustes de reprodução comuns a todos os episódios da sessão
+struct Playback {
+    order: Vec<ServerKind>,   // Ordem de tentativa dos servidores
+    pick_server: bool,
+    quality: Quality,
+    options: PlayerOptions,
+    skipper: Option<AniSkip>, // Presente com --skip-intro
+}
+
+impl Playback {
+    async fn new(args: &Args, session: &Session) -> Self {
+        let order = if args.servers.is_empty() {
+            ServerKind::DEFAULT_ORDER.to_vec()
+        } else {
+            args.servers.clone()
+        };
+
+        let config = Config::load().unwrap_or_else(|e| {
+            eprintln!("Erro ao carregar configuração: {:#}", e);
+            Config::default()
+        });
+        // --player-command sozinho já escolhe o player custom
+        let player = args
+            .player
+            .or(args.player_command.is_some().then_some(PlayerKind::Custom))
+            .or(config.player)
+            .unwrap_or_default();
+
+        let options = PlayerOptions {
+            player,
+            command: args.player_command.clone().or(config.player_command),
+            // O caminho configurado vale para o player da configuração
+            player_path: config.player_path.filter(|_| config.player.is_none_or(|p| p == player)),
+            fullscreen: !args.no_fullscreen,
+            detach: args.detach,
+            exit_after_play: args.exit_after_play,
+            skip_intro: args.skip_intro,
+            skip_title: args.skip_title.clone(),
+            skip_mode: args.skip_mode,
+            nextep_countdown: args.nextep_countdown,
+            sub_langs: args.sub_lang.clone(),
+        };
+
+        let skipper = if options.skip_intro {
+            let title = options.skip_title.as_deref().unwrap_or(&session.anime.title);
+            Some(AniSkip::new(&args.aniskip_url, &args.jikan_url, title).await)
+        } else {
+            None
+        };
+
+        Self { order, pick_server: args.select_server, quality: args.quality, options, skipper }
+    }
+
+    // Trechos a pular no episódio (vazio sem --skip-intro)
+    async fn skips(&self, episode: &EpisodeItem) -> Vec<SkipInterval> {
+        match &self.skipper {
+            Some(skipper) => skipper.episode(&episode.number).await,
+            None => Vec::new(),
+        }
+    }
+
+    // Ajustes do episódio: posição para retomar, se ele ficou pela metade, e
+    // trechos a pular
+    async fn media(&self, history: &WatchHistory, session: &Session, episode: &EpisodeItem) -> MediaOptions {
+        let start = history.resume_position(&session.anime_id, &episode.number);
+        if let Some(pos) = start {
+            println!("Retomando o episódio {} a partir de {}", episode.number, format_timestamp(pos));
+        }
+        MediaOptions {
+            start,
+            skips: self.skips(episode).await,
+            title: Some(format!("{} - Episódio {}", session.anime.title, episode.number)),
+        }
+    }
 
-    let ep_idx = args.episode
-        .and_then(|num| episodes.iter().position(|e| e.number.parse::<usize>().unwrap_or(0) == num))
-        .unwrap_or_else(|| select_from_list(&eps_labels, "Selecione um episódio:").unwrap());
+    // Obtém a fonte de streaming do episódio na qualidade pedida
+    async fn resolve_stream(&self, provider: &dyn AnimeProvider, episode: &EpisodeItem, pick_server: bool) -> Result<StreamSource> {
+        self.resolve_with_server(provider, episode, pick_server).await.map(|(_, source)| source)
+    }
+
+    // Como `resolve_stream`, retornando também o servidor da fonte
+    async fn resolve_with_server(
+        &self,
+        provider: &dyn AnimeProvider,
+        episode: &EpisodeItem,
+        pick_server: bool,
+    ) -> Result<(StreamServer, StreamSource)> {
+        println!("Carregando episódio {}...", episode.number);
+
+        let resolved = if pick_server {
+            select_server(provider, &episode.id).await
+        } else {
+            resolve_sources(provider, &episode.id, &self.order).await
+        };
+
+        let (server, sources) = resolved.map_err(|e| anyhow!("Erro ao obter URL de streaming: {}", e))?;
+        let mut source = select_stream(sources, self.quality).await?;
+        sort_subtitles(&mut source.subtitles, &self.options.sub_langs);
+        Ok((server, source))
+    }
+}
+
+// Obtém a URL de streaming e reproduz o episódio
+async fn play_episode(
+    session: &Session,
+    playback: &Playback,
+    episode: &EpisodeItem,
+    media: &MediaOptions,
+    pick_server: bool,
+) -> Result<PlaybackProgress> {
+    let source = playback.resolve_stream(session.provider.as_ref(), episode, pick_server).await?;
+    let progress = play_video(&source, media, &playback.options)
+        .await
+        .map_err(|e| anyhow!("Erro ao reproduzir vídeo: {}", e))?;
+    println!("Reprodução concluída!");
+    Ok(progress)
+}
 
-    let chosen = &episodes[ep_idx];
-    println!("Carregando episódio {}...", chosen.number);
+// Toca vários episódios na mesma janela do mpv: a seleção de --episode
+// (ex: 1-12) e, no modo maratona (--nextep-countdown), os episódios seguintes
+// do anime. O próximo episódio é resolvido em segundo plano enquanto o atual
+// toca e entra na playlist assim que fica pronto. Retorna o último episódio
+// reproduzido
+async fn play_playlist(
+    session: &Session,
+    playback: &Playback,
+    queue: &[EpisodeItem],
+    history: &mut WatchHistory,
+    pick_server: bool,
+) -> Result<EpisodeItem> {
+    let provider = session.provider.as_ref();
+    let first = &queue[0];
+    let media = playback.media(history, session, first).await;
+    let source = playback.resolve_stream(provider, first, pick_server).await?;
 
-    let stream_url = match get_stream_url(&chosen.id).await {
-        Ok(url) => url,
+    println!("Abrindo reprodutor MPV...");
+    let mut player = match MpvPlayer::launch(&source, &media, &playback.options).await {
+        Ok(player) => player,
         Err(e) => {
-            eprintln!("Erro ao obter URL de streaming: {}", e);
-            return Ok(());
+            println!("Não foi possível abrir o MPV para a playlist ({}), reproduzindo só este episódio", e);
+            let progress = play_video(&source, &media, &playback.options).await?;
+            record_history(history, session, first, &progress)?;
+            return Ok(first.clone());
         }
     };
-    
-    println!("Abrindo reprodutor MPV...");
-    match play_with_mpv(&stream_url) {
-        Ok(_) => println!("Reprodução concluída!"),
-        Err(e) => eprintln!("Erro ao reproduzir vídeo: {}", e),
+
+    let mut current = first.clone();
+    let mut position = 0;
+    loop {
+        let next = match queue.get(position + 1) {
+            Some(episode) => Some(episode.clone()),
+            None if playback.options.nextep_countdown => session.next_episode(&current.number),
+            None => None,
+        };
+
+        let finished = match &next {
+            Some(next) => {
+                let countdown = playback
+                    .options
+                    .nextep_countdown
+                    .then(|| format!("Próximo: episódio {}", next.number));
+
+                // O servidor é sempre escolhido automaticamente em segundo plano
+                let prefetch = async {
+                    let source = playback.resolve_stream(provider, next, false).await;
+                    (source, playback.media(history, session, next).await)
+                };
+                tokio::pin!(prefetch);
+
+                tokio::select! {
+                    // Terminou antes do próximo ficar pronto: nada foi enfileirado
+                    finished = player.watch_to_end(None) => EpisodeFinished { advanced: false, ..finished },
+                    (source, media) = &mut prefetch => match source {
+                        Ok(source) => match player.append(&source, &media).await {
+                            Ok(()) => player.watch_to_end(countdown.as_deref()).await,
+                            Err(e) => {
+                                eprintln!("Erro ao enfileirar o episódio {}: {}", next.number, e);
+                                player.watch_to_end(None).await
+                            }
+                        },
+                        Err(e) => {
+                            eprintln!("Não foi possível carregar o episódio {}: {}", next.number, e);
+                            player.watch_to_end(None).await
+                        }
+                    },
+                }
+            }
+            None => player.watch_to_end(None).await,
+        };
+
+        if let Err(e) = record_history(history, session, &current, &finished.progress) {
+            eprintln!("Erro ao salvar histórico: {}", e);
+        }
+
+        match next {
+            Some(next) if finished.advanced => {
+                println!("Reproduzindo episódio {}...", next.number);
+                current = next;
+                position += 1;
+            }
+            _ => break,
+        }
+    }
+
+    player.close().await?;
+    println!("Reprodução concluída!");
+    Ok(current)
+}
+
+// Toca os episódios escolhidos um depois do outro, abrindo o player de novo
+// a cada um. Usado quando a playlist do mpv não está disponível (outros
+// players ou --detach). Retorna o último episódio reproduzido
+async fn play_sequence(
+    session: &Session,
+    playback: &Playback,
+    queue: &[EpisodeItem],
+    history: &mut WatchHistory,
+    pick_server: bool,
+) -> Result<EpisodeItem> {
+    if playback.options.nextep_countdown {
+        println!("A contagem para o próximo episódio só funciona com o mpv");
     }
-    
+    // Sem esperar o player fechar, os episódios abririam todos juntos
+    let queue = if playback.options.detach && queue.len() > 1 {
+        println!("Com --detach só o primeiro episódio é aberto");
+        &queue[..1]
+    } else {
+        queue
+    };
+
+    let mut last = None;
+    for (idx, episode) in queue.iter().enumerate() {
+        let media = playback.media(history, session, episode).await;
+        let progress = match play_episode(session, playback, episode, &media, pick_server && idx == 0).await {
+            Ok(progress) => progress,
+            // Falhou no meio da lista: volta ao menu no último que tocou
+            Err(e) if last.is_some() => {
+                eprintln!("{}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if let Err(e) = record_history(history, session, episode, &progress) {
+            eprintln!("Erro ao salvar histórico: {}", e);
+        }
+        last = Some(episode.clone());
+    }
+    Ok(last.unwrap_or_else(|| queue[0].clone()))
+}
+
+// Resolve a fonte dos episódios escolhidos (--episode ou menu) e os
+// adiciona à fila de downloads
+async fn enqueue_downloads(args: &Args, output: &Path, template: &str, season: Option<u32>) -> Result<()> {
+    println!("AniRust - Download de episódios");
+    filename::validate(template)?;
+
+    let Some((session, episodes)) = start_from_search(args).await? else {
+        return Ok(());
+    };
+    let playback = Playback::new(args, &session).await;
+
+    let mut added = 0;
+    for episode in &episodes {
+        let (server, source) = match playback
+            .resolve_with_server(session.provider.as_ref(), episode, playback.pick_server)
+            .await
+        {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                eprintln!("Erro ao carregar o episódio {}: {:#}", episode.number, e);
+                continue;
+            }
+        };
+
+        let ext = filename::extension_for(&source.url);
+        let info = filename::FileInfo {
+            title: &session.anime.title,
+            season,
+            episode: &episode.number,
+            episode_title: episode.title.as_deref(),
+            quality: source.quality.as_deref(),
+            audio: session.audio,
+            provider: session.provider.name(),
+            ext: &ext,
+        };
+        let path = output.join(filename::render(template, &info)?);
+        // Sem o ffmpeg, playlists HLS ficam em .ts
+        if path.exists() || path.with_extension("ts").exists() {
+            println!("Já baixado: {}", path.display());
+            continue;
+        }
+
+        // O servidor que resolveu a fonte vai primeiro quando ela for buscada
+        // de novo, para o download continuar dos arquivos parciais
+        let mut servers = playback.order.clone();
+        servers.retain(|kind| *kind != server.kind);
+        servers.insert(0, server.kind);
+        let query = SourceQuery {
+            episode_id: episode.id.clone(),
+            audio: session.audio,
+            quality: playback.quality,
+            servers,
+            server: Some(server.name),
+        };
+        let job = DownloadJob::new(
+            &session.anime.title,
+            &session.anime_id,
+            session.provider.name(),
+            episode.number.clone(),
+            query,
+            source,
+            path,
+        );
+        match DownloadQueue::update(|queue| queue.add(job))? {
+            Some(id) => {
+                println!("[{}] Episódio {} adicionado à fila", id, episode.number);
+                added += 1;
+            }
+            None => println!("Episódio {} já está na fila", episode.number),
+        }
+    }
+
+    println!("{} episódio(s) adicionado(s) à fila", added);
     Ok(())
-}
\ No newline at end of file
+}
+
+async fn run_download_command(action: &DownloadCommand, options: &DownloadOptions, jobs: usize, attempts: u32) -> Result<()> {
+    match action {
+        DownloadCommand::List => {
+            let queue = DownloadQueue::load()?;
+            if queue.jobs.is_empty() {
+                println!("Fila de downloads vazia");
+                return Ok(());
+            }
+
+            for job in &queue.jobs {
+                let error = match (&job.error, job.status) {
+                    (Some(e), JobStatus::Pending | JobStatus::Failed) => {
+                        format!(" ({} falha(s): {})", job.attempts, e)
+                    }
+                    _ => String::new(),
+                };
+                println!(
+                    "[{}] {:<9} {} — episódio {} → {}{}",
+                    job.id,
+                    job.status.label(),
+                    job.anime_title,
+                    job.episode,
+                    queue::job_path(job).display(),
+                    error
+                );
+            }
+        }
+        DownloadCommand::Run => queue::run_queue(options, jobs, attempts).await?,
+        DownloadCommand::Retry => {
+            let count = DownloadQueue::update(|queue| queue.retry_failed())?;
+            println!("{} download(s) de volta na fila", count);
+            if count > 0 {
+                queue::run_queue(options, jobs, attempts).await?;
+            }
+        }
+        DownloadCommand::Clear => {
+            let count = DownloadQueue::update(|queue| queue.clear_finished())?;
+            println!("{} download(s) removido(s) da fila", count);
+        }
+    }
+    Ok(())
+}
+
+// Lista os servidores do episódio e resolve o escolhido pelo usuário
+async fn select_server(provider: &dyn AnimeProvider, episode_id: &str) -> Result<(StreamServer, Vec<StreamSource>)> {
+    let mut servers = provider.servers(episode_id).await?;
+    let names: Vec<String> = servers.iter().map(|s| s.name.clone()).collect();
+    let idx = select_from_list(&names, "Selecione um servidor:")?;
+    let sources = provider.resolve(&servers[idx]).await?;
+    Ok((servers.swap_remove(idx), sources))
+}
@@ -0,0 +1,722 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Modelos compartilhados entre todos os provedores de anime
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimeItem {
+    pub id: String,       // Identificador do anime no provedor (slug, id da API...)
+    pub title: String,    // Título visível do anime
+    #[serde(default)]
+    pub image: String,    // URL da imagem de capa (opcional)
+    #[serde(default)]
+    pub variants: Vec<AnimeVariant>, // Versões legendada/dublada do mesmo anime
+}
+
+impl AnimeItem {
+    // Retorna o ID da versão com o áudio pedido, se existir
+    pub fn variant_id(&self, audio: Audio) -> Option<&str> {
+        self.variants
+            .iter()
+            .find(|v| v.audio == audio)
+            .map(|v| v.id.as_str())
+    }
+
+    // Retorna o ID da versão preferida, ou da primeira disponível
+    pub fn preferred_variant(&self, audio: Audio) -> (Audio, &str) {
+        match self.variant_id(audio) {
+            Some(id) => (audio, id),
+            None => self
+                .variants
+                .first()
+                .map(|v| (v.audio, v.id.as_str()))
+                .unwrap_or((Audio::Sub, self.id.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimeVariant {
+    pub audio: Audio,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Audio {
+    #[default]
+    Sub,
+    Dub,
+}
+
+impl Audio {
+    pub fn other(self) -> Self {
+        match self {
+            Audio::Sub => Audio::Dub,
+            Audio::Dub => Audio::Sub,
+        }
+    }
+}
+
+impl fmt::Display for Audio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Audio::Sub => write!(f, "Legendado"),
+            Audio::Dub => write!(f, "Dublado"),
+        }
+    }
+}
+
+// Agrupa as versões legendada e dublada de um mesmo anime em um único item.
+// Os sites publicam o dublado como outro anime, com o slug terminado em "-dub"
+// e o título com "(Dub)"
+pub fn pair_variants(items: Vec<AnimeItem>) -> Vec<AnimeItem> {
+    let mut paired: Vec<AnimeItem> = Vec::new();
+
+    for item in items {
+        let is_dub = item.id.ends_with("-dub") || item.title.to_lowercase().contains("(dub)");
+        let audio = if is_dub { Audio::Dub } else { Audio::Sub };
+        let base_id = item.id.trim_end_matches("-dub").to_string();
+        let variant = AnimeVariant { audio, id: item.id.clone() };
+
+        match paired.iter_mut().find(|p| p.id.trim_end_matches("-dub") == base_id) {
+            Some(existing) => {
+                if existing.variant_id(audio).is_none() {
+                    existing.variants.push(variant);
+                }
+                // O título e o ID principal vêm da versão legendada
+                if audio == Audio::Sub {
+                    existing.id = item.id;
+                    existing.title = item.title;
+                    existing.image = item.image;
+                }
+            }
+            None => {
+                let title = if is_dub {
+                    item.title.replace("(Dub)", "").replace("(dub)", "").trim().to_string()
+                } else {
+                    item.title
+                };
+                paired.push(AnimeItem {
+                    id: item.id,
+                    title,
+                    image: item.image,
+                    variants: vec![variant],
+                });
+            }
+        }
+    }
+
+    // Mantém a ordem legendado, dublado
+    for item in &mut paired {
+        item.variants.sort_by_key(|v| v.audio == Audio::Dub);
+    }
+
+    paired
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeItem {
+    pub id: String,       // ID do episódio para streaming
+    pub number: EpisodeNumber, // Número do episódio
+    pub title: Option<String>, // Título do episódio (se disponível)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamSource {
+    pub url: String,             // URL direta do vídeo (mp4, m3u8...)
+    pub quality: Option<String>, // Qualidade informada pelo provedor (ex: "1080p")
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>, // Cabeçalhos HTTP exigidos pelo servidor (Referer...)
+    #[serde(default)]
+    pub subtitles: Vec<SubtitleTrack>,     // Legendas externas oferecidas pelo player
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleTrack {
+    pub url: String,           // URL do arquivo de legenda (vtt, srt...)
+    pub label: Option<String>, // Rótulo exibido pelo player (ex: "English")
+}
+
+// Idiomas reconhecidos nos rótulos das legendas: código e nomes comuns
+const SUBTITLE_LANGUAGES: [(&str, &[&str]); 10] = [
+    ("en", &["english", "eng", "inglês", "ingles"]),
+    ("pt", &["portuguese", "português", "portugues", "por", "brazil", "brasil"]),
+    ("es", &["spanish", "español", "espanol", "espanhol", "spa"]),
+    ("ja", &["japanese", "japonês", "japones", "jpn"]),
+    ("fr", &["french", "français", "francais", "francês", "fre", "fra"]),
+    ("de", &["german", "deutsch", "alemão", "ger", "deu"]),
+    ("it", &["italian", "italiano", "ita"]),
+    ("ru", &["russian", "русский", "russo", "rus"]),
+    ("ar", &["arabic", "العربية", "árabe", "ara"]),
+    ("id", &["indonesian", "bahasa", "indonésio", "ind"]),
+];
+
+impl SubtitleTrack {
+    // Código do idioma (ex: "pt"), pelo rótulo ou pelo nome do arquivo
+    // ("Portuguese (Brazil)", "eng.vtt")
+    pub fn language(&self) -> Option<&'static str> {
+        let file = self.url.split(['?', '#']).next().unwrap_or_default();
+        let file = file.rsplit('/').next().unwrap_or_default().to_lowercase();
+        let label = self.label.as_deref().unwrap_or_default().to_lowercase();
+        let words = |text: &str| -> Vec<String> {
+            text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_string).collect()
+        };
+
+        for candidates in [words(&label), words(&file)] {
+            for (code, names) in SUBTITLE_LANGUAGES {
+                if candidates.iter().any(|w| w == code || names.contains(&w.as_str())) {
+                    return Some(code);
+                }
+            }
+        }
+        None
+    }
+
+    // Indica se a legenda é do idioma pedido, por código ("pt") ou nome
+    // ("portuguese")
+    pub fn matches_language(&self, lang: &str) -> bool {
+        let lang = lang.trim().to_lowercase();
+        let code = SUBTITLE_LANGUAGES
+            .iter()
+            .find(|(code, names)| *code == lang || names.contains(&lang.as_str()))
+            .map(|(code, _)| *code);
+        match code {
+            Some(code) => self.language() == Some(code),
+            // Idioma fora da tabela: compara com o rótulo
+            None => self.label.as_deref().is_some_and(|l| l.to_lowercase().contains(&lang)),
+        }
+    }
+
+    // Extensão do arquivo de legenda (vtt, srt, ass...), "vtt" se a URL não
+    // indicar
+    pub fn extension(&self) -> &str {
+        let path = self.url.split(['?', '#']).next().unwrap_or_default();
+        let file = path.rsplit('/').next().unwrap_or_default();
+        match file.rsplit_once('.') {
+            Some((_, ext)) if ["vtt", "srt", "ass", "ssa"].contains(&ext.to_lowercase().as_str()) => ext,
+            _ => "vtt",
+        }
+    }
+}
+
+// Ordena as legendas conforme os idiomas preferidos. As que não aparecem na
+// lista vão para o final, mantendo a ordem do player
+pub fn sort_subtitles(tracks: &mut [SubtitleTrack], langs: &[String]) {
+    tracks.sort_by_key(|t| langs.iter().position(|l| t.matches_language(l)).unwrap_or(langs.len()));
+}
+
+// Qualidade de vídeo pedida pelo usuário: a melhor, a pior ou uma altura
+// específica ("1080", "720p"). Sem a altura exata, usa a mais próxima
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quality {
+    #[default]
+    Best,
+    Worst,
+    Height(u32),
+}
+
+impl Quality {
+    // Extrai a altura de um rótulo de qualidade ("1080p", "720P", "hls 480p")
+    pub fn label_height(label: &str) -> Option<u32> {
+        let re = Regex::new(r"(?i)(\d{3,4})\s*p?\b").unwrap();
+        re.captures(label).and_then(|caps| caps[1].parse().ok())
+    }
+
+    // Escolhe o índice do item com a qualidade mais adequada. Itens sem altura
+    // conhecida só são escolhidos se nenhum outro tiver
+    pub fn select<T>(&self, items: &[T], height: impl Fn(&T) -> Option<u32>) -> Option<usize> {
+        let known: Vec<(usize, u32)> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| height(item).map(|h| (i, h)))
+            .collect();
+
+        if known.is_empty() {
+            return if items.is_empty() { None } else { Some(0) };
+        }
+
+        let chosen = match self {
+            // Em caso de empate, mantém o primeiro item (ordem do provedor)
+            Quality::Best => known.iter().rev().max_by_key(|(_, h)| *h),
+            Quality::Worst => known.iter().min_by_key(|(_, h)| *h),
+            // A mais próxima; no empate, a maior
+            Quality::Height(target) => known
+                .iter()
+                .min_by_key(|(_, h)| (h.abs_diff(*target), std::cmp::Reverse(*h))),
+        };
+
+        chosen.map(|(i, _)| *i)
+    }
+}
+
+impl FromStr for Quality {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "best" | "melhor" => Ok(Quality::Best),
+            "worst" | "pior" => Ok(Quality::Worst),
+            other => other
+                .trim_end_matches('p')
+                .parse::<u32>()
+                .map(Quality::Height)
+                .map_err(|_| anyhow!("Qualidade inválida: \"{}\" (use best, worst ou a altura, ex: 720)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quality::Best => write!(f, "best"),
+            Quality::Worst => write!(f, "worst"),
+            Quality::Height(h) => write!(f, "{}p", h),
+        }
+    }
+}
+
+// Salva como o texto aceito em --quality ("best", "720p")
+impl Serialize for Quality {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Quality {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Servidor de vídeo listado na página do episódio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamServer {
+    pub kind: ServerKind,        // Tipo do servidor, usado para ordenar e escolher
+    pub name: String,            // Nome exibido pelo site (ex: "Vidstreaming")
+    pub url: String,             // URL do player embutido (ou ID usado pela API)
+    pub referer: Option<String>, // Página que embute o player
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerKind {
+    Vidstreaming,
+    Gogo,
+    Streamwish,
+    Filelions,
+    Vidhide,
+    Doodstream,
+    Mp4upload,
+    Other,
+}
+
+impl ServerKind {
+    // Ordem padrão de tentativa dos servidores
+    pub const DEFAULT_ORDER: [ServerKind; 7] = [
+        ServerKind::Vidstreaming,
+        ServerKind::Gogo,
+        ServerKind::Streamwish,
+        ServerKind::Filelions,
+        ServerKind::Vidhide,
+        ServerKind::Doodstream,
+        ServerKind::Mp4upload,
+    ];
+
+    // Identifica o servidor pelo nome ou classe CSS usada no site
+    pub fn from_label(label: &str) -> Self {
+        let label = label.to_lowercase();
+        if label.contains("vidstreaming") || label == "anime" {
+            ServerKind::Vidstreaming
+        } else if label.contains("gogo") || label.contains("vidcdn") {
+            ServerKind::Gogo
+        } else if label.contains("streamwish") || label.contains("wish") {
+            ServerKind::Streamwish
+        } else if label.contains("filelions") {
+            ServerKind::Filelions
+        } else if label.contains("vidhide") {
+            ServerKind::Vidhide
+        } else if label.contains("dood") {
+            ServerKind::Doodstream
+        } else if label.contains("mp4upload") {
+            ServerKind::Mp4upload
+        } else {
+            ServerKind::Other
+        }
+    }
+}
+
+// Ordena os servidores conforme a ordem de preferência. Servidores que não
+// aparecem na lista vão para o final, mantendo a ordem do site
+pub fn sort_servers(servers: &mut [StreamServer], order: &[ServerKind]) {
+    servers.sort_by_key(|s| order.iter().position(|k| *k == s.kind).unwrap_or(order.len()));
+}
+
+// Número de episódio: numérico com fração opcional ("0", "12", "12.5")
+// ou um especial identificado por um rótulo ("OVA", "Special")
+#[derive(Debug, Clone)]
+pub enum EpisodeNumber {
+    Numeric { whole: u32, fraction: Option<String> },
+    Special(String),
+}
+
+impl FromStr for EpisodeNumber {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
+            return Err(anyhow!("Número de episódio vazio"));
+        }
+
+        // Remove prefixos como "EP 12", "Ep. 12" ou "Episode 12"
+        let prefix = Regex::new(r"(?i)^(episódio|episodio|episode|ep)\.?\s*").unwrap();
+        let value = prefix.replace(trimmed, "");
+
+        // Aceita "12", "12.5" e "12,5"
+        let numeric = Regex::new(r"^(\d+)(?:[.,](\d+))?$").unwrap();
+        if let Some(caps) = numeric.captures(&value) {
+            let whole = caps[1]
+                .parse::<u32>()
+                .map_err(|_| anyhow!("Número de episódio inválido: {}", trimmed))?;
+            let fraction = caps
+                .get(2)
+                .map(|m| m.as_str().trim_end_matches('0').to_string())
+                .filter(|f| !f.is_empty());
+            return Ok(EpisodeNumber::Numeric { whole, fraction });
+        }
+
+        Ok(EpisodeNumber::Special(trimmed.to_string()))
+    }
+}
+
+impl fmt::Display for EpisodeNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpisodeNumber::Numeric { whole, fraction: Some(fraction) } => write!(f, "{}.{}", whole, fraction),
+            EpisodeNumber::Numeric { whole, fraction: None } => write!(f, "{}", whole),
+            EpisodeNumber::Special(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+// Ordem: episódios numéricos em ordem crescente (12 < 12.5 < 13),
+// seguidos dos especiais em ordem alfabética
+impl Ord for EpisodeNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (
+                EpisodeNumber::Numeric { whole: a, fraction: fa },
+                EpisodeNumber::Numeric { whole: b, fraction: fb },
+            ) => {
+                // Frações sem zeros à direita podem ser comparadas como texto
+                a.cmp(b).then_with(|| fa.as_deref().unwrap_or("").cmp(fb.as_deref().unwrap_or("")))
+            }
+            (EpisodeNumber::Numeric { .. }, EpisodeNumber::Special(_)) => Ordering::Less,
+            (EpisodeNumber::Special(_), EpisodeNumber::Numeric { .. }) => Ordering::Greater,
+            (EpisodeNumber::Special(a), EpisodeNumber::Special(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        }
+    }
+}
+
+impl PartialOrd for EpisodeNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for EpisodeNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for EpisodeNumber {}
+
+// Serializado sempre como texto; aceita texto ou número na entrada,
+// já que algumas APIs retornam o número do episódio como JSON numérico
+impl Serialize for EpisodeNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for EpisodeNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Integer(u64),
+            Float(f64),
+            Text(String),
+        }
+
+        let text = match Raw::deserialize(deserializer)? {
+            Raw::Integer(n) => n.to_string(),
+            Raw::Float(n) => n.to_string(),
+            Raw::Text(s) => s,
+        };
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Seleção de episódios passada em --episode: "12", "1-12", "3,5,7-9",
+// "10-" (do 10 em diante) ou "-1" (o último; "-2" é o penúltimo...).
+// Intervalos incluem as frações entre os limites (1-12 inclui 5.5), mas não
+// os especiais, que precisam ser pedidos pelo nome ("OVA"). Nos intervalos a
+// fração é escrita com ponto, já que vírgula e hífen são separadores
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpisodeSelection {
+    parts: Vec<SelectionPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SelectionPart {
+    Single(EpisodeNumber),
+    Range(EpisodeNumber, Option<EpisodeNumber>), // Fim aberto em "10-"
+    FromLatest(usize),                           // 1 = último
+}
+
+impl EpisodeSelection {
+    // Episódios que fazem parte da seleção, em ordem crescente e sem repetição
+    pub fn select(&self, episodes: &[EpisodeItem]) -> Vec<EpisodeItem> {
+        let mut sorted: Vec<&EpisodeItem> = episodes.iter().collect();
+        sorted.sort_by(|a, b| a.number.cmp(&b.number));
+
+        let mut selected: Vec<&EpisodeItem> = Vec::new();
+        for part in &self.parts {
+            match part {
+                SelectionPart::Single(number) => {
+                    selected.extend(sorted.iter().filter(|e| &e.number == number));
+                }
+                SelectionPart::Range(start, end) => {
+                    selected.extend(sorted.iter().filter(|e| {
+                        matches!(e.number, EpisodeNumber::Numeric { .. })
+                            && &e.number >= start
+                            && end.as_ref().is_none_or(|end| &e.number <= end)
+                    }));
+                }
+                SelectionPart::FromLatest(n) => {
+                    // Conta só os episódios numerados: especiais não são "o último"
+                    let mut numbered = sorted.iter().rev().filter(|e| matches!(e.number, EpisodeNumber::Numeric { .. }));
+                    if let Some(episode) = numbered.nth(*n - 1) {
+                        selected.push(episode);
+                    }
+                }
+            }
+        }
+
+        selected.sort_by(|a, b| a.number.cmp(&b.number));
+        selected.dedup_by(|a, b| a.number == b.number);
+        selected.into_iter().cloned().collect()
+    }
+}
+
+impl FromStr for EpisodeSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let numeric = |value: &str| -> Result<EpisodeNumber> {
+            match value.parse::<EpisodeNumber>()? {
+                EpisodeNumber::Special(_) => Err(anyhow!("Limite de intervalo inválido: {}", value)),
+                number => Ok(number),
+            }
+        };
+
+        let mut parts = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            if part.is_empty() {
+                return Err(anyhow!("Seleção de episódios inválida: {}", s));
+            }
+
+            let parsed = if let Some(n) = part.strip_prefix('-').and_then(|n| n.parse::<usize>().ok()) {
+                if n == 0 {
+                    return Err(anyhow!("Use -1 para o último episódio"));
+                }
+                SelectionPart::FromLatest(n)
+            } else if let Some((start, end)) = part.split_once('-')
+                && let Ok(start) = numeric(start)
+            {
+                // Especiais com hífen ("OVA-1") não são intervalos
+                let end = match end.trim() {
+                    "" => None,
+                    end => Some(numeric(end)?),
+                };
+                if end.as_ref().is_some_and(|end| end < &start) {
+                    return Err(anyhow!("Intervalo invertido: {}", part));
+                }
+                SelectionPart::Range(start, end)
+            } else {
+                SelectionPart::Single(part.parse()?)
+            };
+            parts.push(parsed);
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+impl fmt::Display for EpisodeSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                SelectionPart::Single(number) => number.to_string(),
+                SelectionPart::Range(start, Some(end)) => format!("{}-{}", start, end),
+                SelectionPart::Range(start, None) => format!("{}-", start),
+                SelectionPart::FromLatest(n) => format!("-{}", n),
+            })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(s: &str) -> EpisodeNumber {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn episode_number_parsing_and_display() {
+        assert_eq!(number("0").to_string(), "0");
+        assert_eq!(number("12").to_string(), "12");
+        assert_eq!(number("12,5").to_string(), "12.5");
+        assert_eq!(number("12.50").to_string(), "12.5");
+        assert_eq!(number("12.0").to_string(), "12");
+        assert_eq!(number("EP 12.5").to_string(), "12.5");
+        assert_eq!(number("Ep. 3").to_string(), "3");
+        assert_eq!(number("Episódio 7").to_string(), "7");
+        // Hífen não é separador decimal: "1-2" não vira 1.2
+        assert!(matches!(number("1-2"), EpisodeNumber::Special(_)));
+        assert!(matches!(number("OVA"), EpisodeNumber::Special(_)));
+        assert!("  ".parse::<EpisodeNumber>().is_err());
+    }
+
+    #[test]
+    fn episode_number_ordering() {
+        let mut numbers: Vec<EpisodeNumber> = ["Special", "13", "ova", "12.5", "0", "12", "12.25", "OVA 2"]
+            .iter()
+            .map(|n| number(n))
+            .collect();
+        numbers.sort();
+        let sorted: Vec<String> = numbers.iter().map(ToString::to_string).collect();
+        assert_eq!(sorted, ["0", "12", "12.25", "12.5", "13", "ova", "OVA 2", "Special"]);
+
+        assert!(number("12") < number("12.25"));
+        assert!(number("12.25") < number("12.5"));
+        assert!(number("12.5") < number("13"));
+        assert!(number("999") < number("OVA"));
+        assert_eq!(number("OVA"), number("ova"));
+        assert_eq!(number("12.5"), number("12,50"));
+    }
+
+    #[test]
+    fn quality_label_height() {
+        assert_eq!(Quality::label_height("1080p (backup)"), Some(1080));
+        assert_eq!(Quality::label_height("hls 480p"), Some(480));
+        assert_eq!(Quality::label_height("720P"), Some(720));
+        assert_eq!(Quality::label_height("auto"), None);
+    }
+
+    #[test]
+    fn quality_selects_nearest_height() {
+        let heights = [Some(360), None, Some(720), Some(1080)];
+        let select = |quality: Quality| quality.select(&heights, |h| *h);
+        assert_eq!(select(Quality::Height(720)), Some(2));
+        assert_eq!(select(Quality::Height(800)), Some(2));
+        assert_eq!(select(Quality::Height(480)), Some(0));
+        // Empate entre 360 e 720: fica com a maior
+        assert_eq!(select(Quality::Height(540)), Some(2));
+        assert_eq!(select(Quality::Best), Some(3));
+        assert_eq!(select(Quality::Worst), Some(0));
+
+        // Sem nenhuma altura conhecida, o primeiro item; sem itens, nenhum
+        assert_eq!(Quality::Best.select(&[None::<u32>, None], |h| *h), Some(0));
+        assert_eq!(Quality::Best.select(&[] as &[Option<u32>], |h| *h), None);
+    }
+
+    #[test]
+    fn quality_ties_keep_provider_order() {
+        let heights = [Some(720), Some(1080), Some(360), Some(1080), Some(360)];
+        assert_eq!(Quality::Best.select(&heights, |h| *h), Some(1));
+        assert_eq!(Quality::Worst.select(&heights, |h| *h), Some(2));
+        assert_eq!(Quality::Height(1000).select(&heights, |h| *h), Some(1));
+    }
+
+    #[test]
+    fn quality_parsing() {
+        assert_eq!("best".parse::<Quality>().unwrap(), Quality::Best);
+        assert_eq!("pior".parse::<Quality>().unwrap(), Quality::Worst);
+        assert_eq!("720p".parse::<Quality>().unwrap(), Quality::Height(720));
+        assert!("alta".parse::<Quality>().is_err());
+    }
+
+    #[test]
+    fn episode_number_json() {
+        let numbers: Vec<EpisodeNumber> = serde_json::from_str(r#"[12, 12.5, "13", "OVA"]"#).unwrap();
+        assert_eq!(numbers, [number("12"), number("12.5"), number("13"), number("OVA")]);
+        assert_eq!(serde_json::to_string(&number("12.5")).unwrap(), r#""12.5""#);
+    }
+
+    fn episodes(numbers: &[&str]) -> Vec<EpisodeItem> {
+        numbers
+            .iter()
+            .map(|n| EpisodeItem { id: format!("ep-{}", n), number: n.parse().unwrap(), title: None })
+            .collect()
+    }
+
+    fn select(selection: &str, available: &[&str]) -> Vec<String> {
+        let selection: EpisodeSelection = selection.parse().unwrap();
+        selection.select(&episodes(available)).iter().map(|e| e.number.to_string()).collect()
+    }
+
+    const AVAILABLE: [&str; 16] = ["1", "2", "3", "4", "5", "5.5", "6", "7", "8", "9", "10", "11", "12", "13", "OVA", "OVA-1"];
+
+    #[test]
+    fn selection_ranges() {
+        assert_eq!(
+            select("1-12", &AVAILABLE),
+            ["1", "2", "3", "4", "5", "5.5", "6", "7", "8", "9", "10", "11", "12"]
+        );
+        assert_eq!(select("3,5,7-9", &AVAILABLE), ["3", "5", "7", "8", "9"]);
+        assert_eq!(select("10-", &AVAILABLE), ["10", "11", "12", "13"]);
+        assert_eq!(select("5.5", &AVAILABLE), ["5.5"]);
+    }
+
+    #[test]
+    fn selection_from_latest_skips_specials() {
+        assert_eq!(select("-1", &AVAILABLE), ["13"]);
+        assert_eq!(select("-2", &AVAILABLE), ["12"]);
+        assert!(select("-20", &AVAILABLE).is_empty());
+    }
+
+    #[test]
+    fn selection_specials_by_name() {
+        assert_eq!(select("OVA-1", &AVAILABLE), ["OVA-1"]);
+        assert_eq!(select("ova", &AVAILABLE), ["OVA"]);
+        assert_eq!(select("12-13,OVA", &AVAILABLE), ["12", "13", "OVA"]);
+    }
+
+    #[test]
+    fn selection_removes_duplicates() {
+        // -10 é o episódio 5 (contando 5.5)
+        assert_eq!(select("3,1-4,3,4-5,-10", &AVAILABLE), ["1", "2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn selection_rejects_invalid() {
+        for invalid in ["5-3", "-0", "", "1,,2", "1-OVA"] {
+            assert!(invalid.parse::<EpisodeSelection>().is_err(), "{}", invalid);
+        }
+    }
+}
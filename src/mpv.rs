@@ -0,0 +1,311 @@
+use anyhow::{Result, anyhow};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::sync::{mpsc, oneshot};
+
+// Respostas aguardadas, indexadas pelo `request_id` enviado
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+/// Evento recebido do mpv pelo socket IPC
+#[derive(Debug, Clone)]
+pub enum MpvEvent {
+    /// Mudança de uma propriedade observada com `observe_property`
+    PropertyChange { name: String, data: Value },
+    /// Mensagem enviada por `script-message` (input.conf, scripts Lua ou
+    /// outros clientes); chega como o evento `client-message` do mpv
+    ClientMessage(Vec<String>),
+    /// Início de um arquivo da playlist, identificado pelo id da entrada
+    StartFile { playlist_entry_id: Option<i64> },
+    /// Fim de um arquivo da playlist (`eof`, `stop`, `quit`, `error`...)
+    EndFile { reason: String },
+    /// Qualquer outro evento (`file-loaded`, `seek`...)
+    Other { name: String },
+}
+
+/// Cliente assíncrono do protocolo JSON IPC do mpv (`--input-ipc-server`).
+/// Os comandos podem ser enviados de várias tarefas ao mesmo tempo; os
+/// eventos são lidos com `next_event` até o mpv fechar o socket
+pub struct MpvClient {
+    writer: tokio::sync::Mutex<OwnedWriteHalf>,
+    events: tokio::sync::Mutex<mpsc::UnboundedReceiver<MpvEvent>>,
+    pending: Pending,
+    next_id: AtomicU64,
+}
+
+impl MpvClient {
+    /// Conecta ao socket IPC informado e começa a ler as mensagens do mpv
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let stream = UnixStream::connect(path.as_ref()).await.map_err(|e| {
+            anyhow!("Falha ao conectar ao IPC do mpv em {}: {}", path.as_ref().display(), e)
+        })?;
+        let (reader, writer) = stream.into_split();
+
+        let pending: Pending = Arc::default();
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+        let reader_pending = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                dispatch(message, &reader_pending, &events_tx);
+            }
+
+            // Conexão fechada: quem ainda aguarda resposta recebe erro
+            for (_, tx) in reader_pending.lock().unwrap().drain() {
+                let _ = tx.send(Err(anyhow!("Conexão com o mpv encerrada")));
+            }
+        });
+
+        Ok(Self {
+            writer: tokio::sync::Mutex::new(writer),
+            events: tokio::sync::Mutex::new(events_rx),
+            pending,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Envia um comando (lista de argumentos ou objeto com argumentos
+    /// nomeados) e aguarda a resposta, retornando o campo `data`
+    pub async fn command(&self, command: Value) -> Result<Value> {
+        let request_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, tx);
+
+        let mut line = json!({ "command": command, "request_id": request_id }).to_string();
+        line.push('\n');
+
+        let written = self.writer.lock().await.write_all(line.as_bytes()).await;
+        if let Err(e) = written {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(anyhow!("Falha ao enviar comando ao mpv: {}", e));
+        }
+
+        rx.await.map_err(|_| anyhow!("Conexão com o mpv encerrada"))?
+    }
+
+    /// Adiciona um arquivo ou URL ao fim da playlist. As opções valem só para
+    /// este arquivo (ex: `start`, `http-header-fields`, `force-media-title`).
+    /// Retorna o id da entrada na playlist, informado pelo mpv 0.38+
+    pub async fn loadfile(&self, url: &str, options: &BTreeMap<String, String>) -> Result<Option<i64>> {
+        let mut command = Map::new();
+        command.insert("name".to_string(), json!("loadfile"));
+        command.insert("url".to_string(), json!(url));
+        command.insert("flags".to_string(), json!("append"));
+        if !options.is_empty() {
+            command.insert("options".to_string(), json!(options));
+        }
+        let data = self.command(Value::Object(command)).await?;
+        Ok(data.get("playlist_entry_id").and_then(Value::as_i64))
+    }
+
+    /// Vai para a posição informada, em segundos
+    pub async fn seek(&self, seconds: f64) -> Result<()> {
+        self.command(json!(["seek", seconds, "absolute"])).await.map(|_| ())
+    }
+
+    /// Lê o valor atual de uma propriedade (ex: `playlist/0/id`)
+    pub async fn get_property(&self, name: &str) -> Result<Value> {
+        self.command(json!(["get_property", name])).await
+    }
+
+    /// Mostra uma mensagem na tela do player (OSD)
+    pub async fn show_text(&self, text: &str, duration_ms: u64) -> Result<()> {
+        self.command(json!(["show-text", text, duration_ms])).await.map(|_| ())
+    }
+
+    /// Carrega uma legenda externa no arquivo atual. Com `select`, ela passa a
+    /// ser a legenda exibida
+    pub async fn sub_add(&self, url: &str, select: bool, title: &str, lang: &str) -> Result<()> {
+        let flag = if select { "select" } else { "auto" };
+        self.command(json!(["sub-add", url, flag, title, lang])).await.map(|_| ())
+    }
+
+    /// Associa uma tecla a um comando do mpv (ex: `script-message nome`,
+    /// que chega como `MpvEvent::ClientMessage`). Requer mpv 0.37+
+    pub async fn keybind(&self, key: &str, command: &str) -> Result<()> {
+        self.command(json!(["keybind", key, command])).await.map(|_| ())
+    }
+
+    /// Passa a receber `MpvEvent::PropertyChange` para a propriedade
+    pub async fn observe_property(&self, name: &str) -> Result<()> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.command(json!(["observe_property", id, name])).await.map(|_| ())
+    }
+
+    /// Próximo evento do mpv; `None` quando a conexão é encerrada
+    pub async fn next_event(&self) -> Option<MpvEvent> {
+        self.events.lock().await.recv().await
+    }
+}
+
+// Encaminha uma mensagem recebida: respostas vão para quem enviou o
+// comando, eventos vão para a fila de eventos
+fn dispatch(message: Value, pending: &Pending, events: &mpsc::UnboundedSender<MpvEvent>) {
+    if let Some(request_id) = message.get("request_id").and_then(Value::as_u64) {
+        let Some(tx) = pending.lock().unwrap().remove(&request_id) else {
+            return;
+        };
+        let result = match message["error"].as_str() {
+            Some("success") | None => Ok(message.get("data").cloned().unwrap_or(Value::Null)),
+            Some(error) => Err(anyhow!("mpv recusou o comando: {}", error)),
+        };
+        let _ = tx.send(result);
+        return;
+    }
+
+    let Some(name) = message.get("event").and_then(Value::as_str) else {
+        return;
+    };
+    let event = match name {
+        "property-change" => MpvEvent::PropertyChange {
+            name: message["name"].as_str().unwrap_or_default().to_string(),
+            data: message.get("data").cloned().unwrap_or(Value::Null),
+        },
+        "client-message" => MpvEvent::ClientMessage(
+            message["args"]
+                .as_array()
+                .map(|args| args.iter().filter_map(|a| a.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+        ),
+        "start-file" => MpvEvent::StartFile {
+            playlist_entry_id: message["playlist_entry_id"].as_i64(),
+        },
+        "end-file" => MpvEvent::EndFile {
+            reason: message["reason"].as_str().unwrap_or_default().to_string(),
+        },
+        _ => MpvEvent::Other { name: name.to_string() },
+    };
+    let _ = events.send(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    // Socket numa pasta temporária própria do teste
+    fn listen(name: &str) -> (UnixListener, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("anirust-mpv-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("socket");
+        (UnixListener::bind(&path).unwrap(), path)
+    }
+
+    // Lê um pedido do cliente: o `request_id` e o comando
+    async fn read_request(lines: &mut tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>) -> (u64, Value) {
+        let line = lines.next_line().await.unwrap().unwrap();
+        let message: Value = serde_json::from_str(&line).unwrap();
+        (message["request_id"].as_u64().unwrap(), message["command"].clone())
+    }
+
+    #[tokio::test]
+    async fn replies_reach_their_commands_out_of_order() {
+        let (listener, path) = listen("replies");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                requests.push(read_request(&mut lines).await);
+            }
+            // Responde na ordem inversa; cada resposta devolve o argumento do
+            // comando, e "fail" é recusado
+            for (request_id, command) in requests.into_iter().rev() {
+                let reply = match command[1].as_str() {
+                    Some("fail") => json!({ "request_id": request_id, "error": "invalid parameter" }),
+                    _ => json!({ "request_id": request_id, "error": "success", "data": command[1] }),
+                };
+                writer.write_all(format!("{}\n", reply).as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = MpvClient::connect(&path).await.unwrap();
+        let (first, second, failed) = tokio::join!(
+            client.command(json!(["echo", "primeiro"])),
+            client.command(json!(["echo", "segundo"])),
+            client.command(json!(["echo", "fail"])),
+        );
+        assert_eq!(first.unwrap(), json!("primeiro"));
+        assert_eq!(second.unwrap(), json!("segundo"));
+        let error = failed.unwrap_err().to_string();
+        assert!(error.contains("invalid parameter"), "{}", error);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn events_are_parsed() {
+        let (listener, path) = listen("events");
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let events = [
+                json!({ "event": "property-change", "id": 1, "name": "time-pos", "data": 12.5 }),
+                json!({ "event": "client-message", "args": ["anirust-skip", "op"] }),
+                json!({ "event": "end-file", "reason": "eof", "playlist_entry_id": 1 }),
+                json!({ "event": "start-file", "playlist_entry_id": 2 }),
+                json!({ "event": "file-loaded" }),
+            ];
+            for event in events {
+                stream.write_all(format!("{}\n", event).as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = MpvClient::connect(&path).await.unwrap();
+        match client.next_event().await {
+            Some(MpvEvent::PropertyChange { name, data }) => {
+                assert_eq!(name, "time-pos");
+                assert_eq!(data, json!(12.5));
+            }
+            other => panic!("esperava property-change: {:?}", other),
+        }
+        match client.next_event().await {
+            Some(MpvEvent::ClientMessage(args)) => assert_eq!(args, ["anirust-skip", "op"]),
+            other => panic!("esperava client-message: {:?}", other),
+        }
+        match client.next_event().await {
+            Some(MpvEvent::EndFile { reason }) => assert_eq!(reason, "eof"),
+            other => panic!("esperava end-file: {:?}", other),
+        }
+        match client.next_event().await {
+            Some(MpvEvent::StartFile { playlist_entry_id }) => assert_eq!(playlist_entry_id, Some(2)),
+            other => panic!("esperava start-file: {:?}", other),
+        }
+        match client.next_event().await {
+            Some(MpvEvent::Other { name }) => assert_eq!(name, "file-loaded"),
+            other => panic!("esperava file-loaded: {:?}", other),
+        }
+        server.await.unwrap();
+        assert!(client.next_event().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn pending_commands_fail_when_the_socket_closes() {
+        let (listener, path) = listen("close");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            // Recebe o comando e fecha sem responder
+            read_request(&mut lines).await;
+            drop(writer);
+            drop(lines);
+        });
+
+        let client = MpvClient::connect(&path).await.unwrap();
+        let result = client.command(json!(["get_property", "pause"])).await;
+        assert!(result.is_err());
+        server.await.unwrap();
+        assert!(client.next_event().await.is_none());
+    }
+}
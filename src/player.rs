@@ -1,309 +1,866 @@
 use anyhow::{anyhow, Result, Context};
-use std::process::{Command, Stdio};
-use std::path::Path;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::download::{download_file, download_hls, DownloadOptions};
+use crate::hls::is_hls;
+use crate::models::{StreamSource, SubtitleTrack};
+use crate::mpv::{MpvClient, MpvEvent};
+use crate::skip::{chapters_metadata, SkipInterval, SkipMode};
+use crate::discovery::{find_player, resolve_program, PlayerCommand, PlayerSpec, FFPLAY, MPV, VLC};
+use crate::utils::split_command;
+
+// Segundos antes do fim em que a contagem para o próximo episódio começa
+const NEXTEP_COUNTDOWN: f64 = 10.0;
+
+// Tecla que pula a abertura/encerramento no modo `--skip-mode key`
+const SKIP_KEY: &str = "TAB";
+
+// Mensagem enviada pelo mpv quando a tecla de pulo é apertada
+const SKIP_MESSAGE: &str = "anirust-skip";
+
+/// Programa usado para reproduzir os vídeos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerKind {
+    #[default]
+    Mpv,
+    Vlc,
+    Ffplay,
+    Custom, // Comando definido pelo usuário (--player-command)
+}
 
 // Estrutura para opções do player
 #[derive(Debug, Clone)]
 pub struct PlayerOptions {
+    pub player: PlayerKind,
+    pub command: Option<String>, // Modelo do comando do player custom
+    pub player_path: Option<PathBuf>, // Caminho do player definido na configuração
     pub fullscreen: bool,
-    pub no_detach: bool,
+    pub detach: bool,            // Não espera o player fechar (o progresso não é salvo)
     pub exit_after_play: bool,
     pub skip_intro: bool,
     pub skip_title: Option<String>,
+    pub skip_mode: SkipMode,
     pub nextep_countdown: bool,
+    pub sub_langs: Vec<String>, // Idiomas de legenda preferidos, em ordem
 }
 
 impl Default for PlayerOptions {
     fn default() -> Self {
         Self {
+            player: PlayerKind::default(),
+            command: None,
+            player_path: None,
             fullscreen: true,
-            no_detach: false,
+            detach: false,
             exit_after_play: false,
             skip_intro: false,
             skip_title: None,
+            skip_mode: SkipMode::default(),
             nextep_countdown: false,
+            sub_langs: Vec::new(),
         }
     }
 }
 
-// Função para reproduzir vídeo com MPV
-pub fn play_with_mpv(stream_url: &str) -> Result<()> {
-    println!("Iniciando reprodução do vídeo...");
-    
-    // Tentar reproduzir diretamente com MPV primeiro
-    if let Ok(mpv_path) = find_mpv() {
-        println!("Usando MPV para reprodução...");
-        
-        // Configurar argumentos para o MPV
-        let mut args = vec![
-            "--no-terminal",     // Não usa o terminal para output
-            "--msg-level=all=info", // Nível de log informativo
-            "--hwdec=no",        // Desativa aceleração de hardware
-            "--vo=x11",          // Usa o driver de saída X11 (sem aceleração)
-            "--gpu-context=x11", // Contexto X11 para GPU
-            "--opengl-backend=x11", // Backend OpenGL X11
-            "--fs",              // Inicia em tela cheia
-            "--force-window=yes", // Força a abertura da janela
-            "--keep-open=yes",   // Mantém a janela aberta após o término
-            "--ytdl=no",         // Desativa o uso interno do youtube-dl
-        ];
-        
-        // Adicionar URL do stream
-        args.push(stream_url);
-        
-        // Executar MPV
-        match Command::new(&mpv_path)
-            .args(&args)
-            .spawn()
-            .and_then(|mut child| child.wait()) {
-            Ok(_) => return Ok(()),
-            Err(e) => println!("Erro ao usar MPV: {}", e),
+// Ajustes de um episódio tocado pelo player
+#[derive(Debug, Clone, Default)]
+pub struct MediaOptions {
+    pub start: Option<f64>,        // Posição inicial, em segundos
+    pub skips: Vec<SkipInterval>,  // Trechos a pular (abertura, encerramento...)
+    pub title: Option<String>,     // Título exibido na janela do player
+}
+
+// Progresso da reprodução informado pelo mpv. Os campos ficam vazios quando
+// o player não permite acompanhar a reprodução (ffplay, por exemplo)
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackProgress {
+    pub position: Option<f64>,  // Última posição conhecida, em segundos
+    pub duration: Option<f64>,  // Duração do vídeo, em segundos
+    pub reached_end: bool,      // O vídeo foi assistido até o fim
+}
+
+impl PlaybackProgress {
+    // Porcentagem assistida, quando a duração é conhecida
+    pub fn completion(&self) -> Option<f32> {
+        if self.reached_end {
+            return Some(100.0);
         }
-    } else {
-        println!("MPV não encontrado, tentando alternativas...");
-    }
-    
-    // Se MPV falhar ou não estiver disponível, tentar ffplay
-    if let Ok(ffplay_path) = find_ffplay() {
-        println!("Tentando reproduzir com ffplay...");
-        match Command::new(&ffplay_path)
-            .args(&[
-                "-autoexit",
-                "-fs",
-                "-nodisp",           // Desativa a exibição de informações
-                "-vf", "format=yuv420p", // Força formato de pixel compatível
-                "-x", "1280",        // Largura fixa
-                "-y", "720",         // Altura fixa
-                "-sws_flags", "bilinear", // Algoritmo de escala simples
-                "-loglevel", "warning", // Reduz logs
-                stream_url
-            ])
-            .spawn()
-            .and_then(|mut child| child.wait()) {
-            Ok(_) => return Ok(()),
-            Err(e) => println!("Erro ao usar ffplay: {}", e),
+        let duration = self.duration.filter(|d| *d > 0.0)?;
+        let position = self.position.unwrap_or(0.0);
+        Some((position / duration * 100.0).clamp(0.0, 100.0) as f32)
+    }
+}
+
+/// Interface comum para os programas de reprodução (mpv, VLC, ffplay...)
+#[async_trait]
+pub trait Player: Send + Sync {
+    /// Nome exibido nas mensagens
+    fn name(&self) -> &'static str;
+
+    /// Reproduz a fonte e, sem `detach`, espera o player fechar. Só o mpv
+    /// informa o progresso; os outros retornam um progresso vazio
+    async fn play(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress>;
+}
+
+// Obtém o backend escolhido nas opções, já com o caminho do programa
+pub fn get_player(options: &PlayerOptions) -> Result<Box<dyn Player>> {
+    let find = |spec: &PlayerSpec| {
+        find_player(spec, options.player_path.as_deref())
+            .map_err(|e| anyhow!("{}\nUse --player para escolher outro player", e))
+    };
+    let player: Box<dyn Player> = match options.player {
+        PlayerKind::Mpv => Box::new(MpvBackend { command: find(&MPV)? }),
+        PlayerKind::Vlc => Box::new(VlcBackend { command: find(&VLC)? }),
+        PlayerKind::Ffplay => Box::new(FfplayBackend { command: find(&FFPLAY)? }),
+        PlayerKind::Custom => {
+            let template = options
+                .command
+                .as_deref()
+                .ok_or_else(|| anyhow!("O player custom precisa de um comando (--player-command \"prog {{url}}\")"))?;
+            Box::new(CustomBackend::new(template)?)
         }
-    } else {
-        println!("ffplay não encontrado, tentando alternativas...");
+    };
+    Ok(player)
+}
+
+/// Reproduz o vídeo com o player escolhido. Se o player não conseguir abrir o
+/// stream, o vídeo é baixado e reproduzido a partir do arquivo local
+pub async fn play_video(source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress> {
+    let player = get_player(options)?;
+    println!("Abrindo reprodutor {}...", player.name());
+    if !media.skips.is_empty() && options.player != PlayerKind::Mpv {
+        println!("Pular aberturas e encerramentos só funciona com o mpv");
     }
-    
-    // Se tudo falhar, tentar baixar o vídeo e reproduzir localmente
+
+    let error = match player.play(source, media, options).await {
+        Ok(progress) => return Ok(progress),
+        Err(e) => e,
+    };
+    println!("Erro ao usar {}: {}", player.name(), error);
+
+    // Se o player não abrir o stream, tentar baixar o vídeo e reproduzir localmente
     println!("Tentando baixar o vídeo e reproduzir localmente...");
-    
-    let temp_file = "/tmp/anirust_video.mp4";
-    
-    // Verificar se o diretório /tmp existe
-    if !Path::new("/tmp").exists() {
-        std::fs::create_dir_all("/tmp").context("Falha ao criar diretório temporário")?;
-    }
-    
-    // Baixar o vídeo
-    match Command::new("curl")
-        .args(&["-L", "-o", temp_file, stream_url])
-        .spawn()
-        .and_then(|mut child| child.wait()) {
-        Ok(status) => {
-            if status.success() {
-                println!("Download concluído, tentando reproduzir o arquivo local...");
-                
-                // Tentar reproduzir com MPV novamente
-                if let Ok(mpv_path) = find_mpv() {
-                    match Command::new(&mpv_path)
-                        .args(&[
-                            "--no-terminal",
-                            "--hwdec=no",    // Desativa aceleração de hardware
-                            "--vo=x11",      // Usa o driver de saída X11 (sem aceleração)
-                            "--fs",
-                            temp_file
-                        ])
-                        .spawn()
-                        .and_then(|mut child| child.wait()) {
-                        Ok(_) => {
-                            // Remover o arquivo temporário
-                            let _ = std::fs::remove_file(temp_file);
-                            return Ok(());
-                        },
-                        Err(e) => println!("Erro ao reproduzir arquivo local com MPV: {}", e),
-                    }
-                }
-                
-                // Se MPV falhar, tentar com ffplay
-                if let Ok(ffplay_path) = find_ffplay() {
-                    match Command::new(&ffplay_path)
-                        .args(&[
-                            "-autoexit",
-                            "-fs",
-                            temp_file
-                        ])
-                        .spawn()
-                        .and_then(|mut child| child.wait()) {
-                        Ok(_) => {
-                            // Remover o arquivo temporário
-                            let _ = std::fs::remove_file(temp_file);
-                            return Ok(());
-                        },
-                        Err(e) => println!("Erro ao reproduzir arquivo local com ffplay: {}", e),
-                    }
-                }
-                
-                // Se tudo falhar, pelo menos informar onde o arquivo foi baixado
-                println!("Não foi possível reproduzir o vídeo, mas ele foi baixado em: {}", temp_file);
-                return Ok(());
-            } else {
-                return Err(anyhow!("Falha ao baixar o vídeo"));
+    let temp_file = std::env::temp_dir().join("anirust_video.mp4");
+    let downloaded = download_video(source, &temp_file, &DownloadOptions::default())
+        .await
+        .context("Falha ao baixar o vídeo")?;
+    println!("Download concluído, tentando reproduzir o arquivo local...");
+
+    let local = StreamSource {
+        url: downloaded.display().to_string(),
+        ..source.clone()
+    };
+    match player.play(&local, media, options).await {
+        Ok(progress) => {
+            // Com --detach o player ainda está usando o arquivo
+            if !options.detach {
+                let _ = std::fs::remove_file(&downloaded);
+            }
+            Ok(progress)
+        }
+        Err(e) => Err(anyhow!(
+            "{} (o vídeo foi baixado em: {})",
+            e,
+            downloaded.display()
+        )),
+    }
+}
+
+// Executa um player que não é acompanhado pelo IPC. Com `detach`, o player
+// fica aberto em segundo plano e o terminal é liberado na hora
+async fn run_player(mut command: tokio::process::Command, options: &PlayerOptions) -> Result<PlaybackProgress> {
+    if options.detach {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0) // Não recebe o Ctrl+C do terminal
+            .spawn()?;
+        println!("Player aberto em segundo plano; o progresso não será salvo");
+        return Ok(PlaybackProgress::default());
+    }
+
+    let status = command.status().await?;
+    if !status.success() {
+        return Err(anyhow!("o player terminou com {}", status));
+    }
+    Ok(PlaybackProgress::default())
+}
+
+/// mpv, acompanhado pelo socket IPC: salva a posição, pula trechos e carrega
+/// legendas
+pub struct MpvBackend {
+    command: PlayerCommand,
+}
+
+#[async_trait]
+impl Player for MpvBackend {
+    fn name(&self) -> &'static str {
+        "MPV"
+    }
+
+    async fn play(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress> {
+        let args = mpv_stream_args(source, media, options);
+        if options.detach {
+            let mut command = self.command.command();
+            command.args(&args);
+            return run_player(command, options).await;
+        }
+        run_mpv(&self.command, &args, media, options).await
+    }
+}
+
+/// VLC. Aceita só Referer e User-Agent como cabeçalhos
+pub struct VlcBackend {
+    command: PlayerCommand,
+}
+
+#[async_trait]
+impl Player for VlcBackend {
+    fn name(&self) -> &'static str {
+        "VLC"
+    }
+
+    async fn play(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress> {
+        let mut command = self.command.command();
+        command.args(["--no-video-title-show", "--play-and-exit"]);
+        if options.fullscreen {
+            command.arg("--fullscreen");
+        }
+        if let Some(start) = media.start {
+            command.arg(format!("--start-time={:.1}", start));
+        }
+        if let Some(title) = &media.title {
+            command.arg(format!("--meta-title={}", title));
+        }
+        // O VLC não abre legendas por URL; o idioma vale para as embutidas
+        if !options.sub_langs.is_empty() {
+            command.arg(format!("--sub-language={}", options.sub_langs.join(",")));
+        }
+        command.args(vlc_header_args(&source.headers)).arg(&source.url);
+        run_player(command, options).await
+    }
+}
+
+/// ffplay, o player simples que acompanha o ffmpeg
+pub struct FfplayBackend {
+    command: PlayerCommand,
+}
+
+#[async_trait]
+impl Player for FfplayBackend {
+    fn name(&self) -> &'static str {
+        "ffplay"
+    }
+
+    async fn play(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress> {
+        let mut command = self.command.command();
+        command.args([
+            "-autoexit",
+            "-vf", "format=yuv420p", // Força formato de pixel compatível
+            "-x", "1280",        // Largura da janela fora da tela cheia
+            "-y", "720",         // Altura da janela fora da tela cheia
+            "-sws_flags", "bilinear", // Algoritmo de escala simples
+            "-loglevel", "warning", // Reduz logs
+        ]);
+        if options.fullscreen {
+            command.arg("-fs");
+        }
+        if let Some(start) = media.start {
+            command.args(["-ss".to_string(), format!("{:.1}", start)]);
+        }
+        if let Some(title) = &media.title {
+            command.args(["-window_title", title]);
+        }
+        command.args(ffplay_header_args(&source.headers)).arg(&source.url);
+        run_player(command, options).await
+    }
+}
+
+/// Player definido pelo usuário a partir de um modelo de comando, com os
+/// campos `{url}`, `{title}`, `{referer}`, `{start}` (posição inicial em
+/// segundos), `{sub}` (URL da legenda preferida) e `{fullscreen}` (`yes` ou
+/// `no`; `{fullscreen:texto}` vira o texto só em tela cheia). Ex:
+/// `celluloid {url}`. Os campos são trocados depois de separar os
+/// argumentos, então valores com espaços continuam sendo um argumento só
+pub struct CustomBackend {
+    program: PathBuf,
+    args: Vec<String>,
+}
+
+impl CustomBackend {
+    pub fn new(template: &str) -> Result<Self> {
+        let mut parts = split_command(template)?.into_iter();
+        let program = parts.next().ok_or_else(|| anyhow!("Comando do player vazio"))?;
+        let args: Vec<String> = parts.collect();
+        if !args.iter().any(|a| a.contains("{url}")) {
+            return Err(anyhow!("O comando do player precisa do campo {{url}}: {}", template));
+        }
+        let program = resolve_program(Path::new(&program))
+            .map_err(|reason| anyhow!("Player {} não encontrado: {}", program, reason))?;
+        Ok(Self { program, args })
+    }
+
+    fn uses(&self, field: &str) -> bool {
+        self.args.iter().any(|a| a.contains(field))
+    }
+
+    // Avisa das opções pedidas que o comando não tem como receber
+    fn warn_unsupported(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) {
+        if !options.fullscreen && !self.uses("{fullscreen") {
+            println!("O comando do player não tem o campo {{fullscreen}}; --no-fullscreen não tem efeito");
+        }
+        if media.start.is_some() && !self.uses("{start}") {
+            println!("O comando do player não tem o campo {{start}}; o episódio começa do início");
+        }
+        if !source.subtitles.is_empty() && !self.uses("{sub}") {
+            println!("O comando do player não tem o campo {{sub}}; as legendas externas não serão carregadas");
+        }
+    }
+}
+
+#[async_trait]
+impl Player for CustomBackend {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    async fn play(&self, source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<PlaybackProgress> {
+        self.warn_unsupported(source, media, options);
+
+        let referer = source
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("referer"))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default();
+        let start = media.start.map(|start| format!("{:.1}", start)).unwrap_or_default();
+        let fields = [
+            ("{url}", source.url.as_str()),
+            ("{title}", media.title.as_deref().unwrap_or_default()),
+            ("{referer}", referer),
+            ("{start}", start.as_str()),
+            ("{sub}", source.subtitles.first().map(|t| t.url.as_str()).unwrap_or_default()),
+            ("{fullscreen}", if options.fullscreen { "yes" } else { "no" }),
+        ];
+
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(self.args.iter().filter_map(|arg| expand_arg(arg, &fields, options.fullscreen)));
+        run_player(command, options).await
+    }
+}
+
+// Troca os campos de um argumento do comando custom. Argumentos com um campo
+// sem valor (ex: "--referrer={referer}" sem Referer) e com
+// `{fullscreen:...}` fora da tela cheia são omitidos
+fn expand_arg(arg: &str, fields: &[(&str, &str)], fullscreen: bool) -> Option<String> {
+    let mut value = arg.to_string();
+    while let Some(pos) = value.find("{fullscreen:") {
+        // Sem a chave de fechamento o texto fica como está
+        let Some(end) = value[pos..].find('}').map(|end| pos + end) else {
+            break;
+        };
+        if !fullscreen {
+            return None;
+        }
+        let text = value[pos + "{fullscreen:".len()..end].to_string();
+        value.replace_range(pos..=end, &text);
+    }
+
+    for (field, replacement) in fields {
+        if value.contains(field) {
+            if replacement.is_empty() {
+                return None;
             }
-        },
+            value = value.replace(field, replacement);
+        }
+    }
+    Some(value)
+}
+
+// Argumentos do mpv para tocar a fonte: ajustes de compatibilidade,
+// cabeçalhos, legendas e os ajustes do episódio
+fn mpv_stream_args(source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "--no-terminal",     // Não usa o terminal para output
+        "--msg-level=all=info", // Nível de log informativo
+        "--hwdec=no",        // Desativa aceleração de hardware
+        "--vo=x11",          // Usa o driver de saída X11 (sem aceleração)
+        "--gpu-context=x11", // Contexto X11 para GPU
+        "--opengl-backend=x11", // Backend OpenGL X11
+        "--force-window=yes", // Força a abertura da janela
+        "--keep-open=yes",   // Mantém a janela aberta após o término
+        "--ytdl=no",         // Desativa o uso interno do youtube-dl
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    if options.fullscreen {
+        args.push("--fs".to_string());
+    }
+    if let Some(start) = media.start {
+        args.push(format!("--start={:.1}", start));
+    }
+    if let Some(title) = &media.title {
+        args.push(format!("--force-media-title={}", title));
+    }
+
+    // Cabeçalhos exigidos pelo servidor (Referer, User-Agent...)
+    args.extend(mpv_header_args(&source.headers));
+
+    // Legendas externas, já na ordem de preferência; o --slang ajuda o mpv a
+    // escolher entre elas e as legendas embutidas no vídeo
+    args.extend(source.subtitles.iter().map(|track| format!("--sub-file={}", track.url)));
+    if !options.sub_langs.is_empty() {
+        args.push(format!("--slang={}", options.sub_langs.join(",")));
+    }
+
+    // Adicionar URL do stream
+    args.push(source.url.clone());
+    args
+}
+
+// Executa o mpv com um socket IPC e acompanha a reprodução até o player fechar
+async fn run_mpv<S: AsRef<OsStr>>(
+    mpv: &PlayerCommand,
+    args: &[S],
+    media: &MediaOptions,
+    options: &PlayerOptions,
+) -> Result<PlaybackProgress> {
+    let mut player = MpvPlayer::spawn(mpv, args, media, options).await?;
+    let finished = player.watch_to_end(None).await;
+    player.close().await?;
+    Ok(finished.progress)
+}
+
+// Caminho do socket IPC do mpv, único por processo
+fn ipc_socket_path(dir: &Path) -> PathBuf {
+    dir.join(format!("anirust-mpv-{}.sock", std::process::id()))
+}
+
+// Grava os trechos como capítulos para o --chapters-file do mpv
+fn write_chapters(dir: &Path, skips: &[SkipInterval], index: usize) -> Option<PathBuf> {
+    if skips.is_empty() {
+        return None;
+    }
+    let path = dir.join(format!("anirust-chapters-{}-{}.ffmeta", std::process::id(), index));
+    match std::fs::write(&path, chapters_metadata(skips)) {
+        Ok(()) => Some(path),
         Err(e) => {
-            return Err(anyhow!("Erro ao iniciar download: {}", e));
-        }
-    }
-}
-
-// Função para reproduzir vídeo com VLC
-pub fn play_with_vlc(stream_url: &str) -> Result<()> {
-    // Verificar se vlc está instalado
-    match find_vlc() {
-        Ok(vlc_path) => {
-            println!("Iniciando VLC...");
-            
-            // Executar VLC
-            match Command::new(&vlc_path)
-                .args(&[
-                    "--fullscreen",
-                    "--no-video-title-show",
-                    stream_url
-                ])
-                .spawn()
-                .and_then(|mut child| child.wait()) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(anyhow!("Erro ao executar VLC: {}", e)),
+            println!("Falha ao gravar capítulos: {}", e);
+            None
+        }
+    }
+}
+
+/// Instância do mpv controlada pelo IPC. Acompanha o episódio atual e permite
+/// enfileirar os próximos na mesma janela
+pub struct MpvPlayer {
+    child: tokio::process::Child,
+    client: Option<MpvClient>,   // Ausente se o IPC não respondeu
+    socket: PathBuf,
+    dir: PathBuf,                // Pasta visível para o mpv (socket e capítulos)
+    progress: PlaybackProgress,  // Progresso do arquivo atual da playlist
+    skip_mode: SkipMode,
+    skips: Vec<SkipInterval>,                // Trechos do arquivo atual
+    entries: HashMap<i64, PlaylistEntry>,    // Ajustes de cada arquivo, pelo id na playlist do mpv
+    pending_subtitles: Vec<SubtitleTrack>,   // Legendas a carregar quando o arquivo abrir
+    sub_langs: Vec<String>,
+    skipped: Vec<usize>,                     // Trechos já pulados/anunciados
+    active_skip: Option<usize>,              // Trecho em que o vídeo está agora
+    chapter_files: Vec<PathBuf>,
+}
+
+// Ajustes de um arquivo da playlist, aplicados sempre que ele começa a tocar
+// (inclusive ao voltar para ele)
+#[derive(Debug, Clone, Default)]
+struct PlaylistEntry {
+    skips: Vec<SkipInterval>,
+    subtitles: Vec<SubtitleTrack>, // Legendas carregadas pelo IPC
+}
+
+/// Como terminou o acompanhamento de um episódio
+#[derive(Debug, Clone)]
+pub struct EpisodeFinished {
+    pub progress: PlaybackProgress,
+    pub advanced: bool,  // O mpv passou para o próximo item da playlist
+}
+
+impl MpvPlayer {
+    /// Abre o mpv tocando a fonte com os ajustes do episódio
+    pub async fn launch(source: &StreamSource, media: &MediaOptions, options: &PlayerOptions) -> Result<Self> {
+        let mpv = find_player(&MPV, options.player_path.as_deref())?;
+        Self::spawn(&mpv, &mpv_stream_args(source, media, options), media, options).await
+    }
+
+    async fn spawn<S: AsRef<OsStr>>(
+        mpv: &PlayerCommand,
+        args: &[S],
+        media: &MediaOptions,
+        options: &PlayerOptions,
+    ) -> Result<Self> {
+        let dir = mpv.shared_dir()?;
+        let socket = ipc_socket_path(&dir);
+        let _ = std::fs::remove_file(&socket);
+
+        let mut command = mpv.command();
+        command.arg(format!("--input-ipc-server={}", socket.display()));
+        let chapters = write_chapters(&dir, &media.skips, 0);
+        if let Some(path) = &chapters {
+            command.arg(format!("--chapters-file={}", path.display()));
+        }
+        let mut child = command.args(args).spawn()?;
+
+        // O mpv cria o socket logo depois de iniciar; espera até 10s por ele,
+        // desistindo antes se o processo terminar
+        let mut client = None;
+        for _ in 0..100 {
+            if child.try_wait()?.is_some() {
+                break;
             }
-        },
-        Err(_) => {
-            // Se VLC não estiver disponível, usar MPV como fallback
-            println!("VLC não encontrado, usando MPV como alternativa...");
-            play_with_mpv(stream_url)
-        }
-    }
-}
-
-// Função para baixar vídeo
-pub fn download_video(stream_url: &str, output_path: &str) -> Result<()> {
-    println!("Baixando vídeo para: {}", output_path);
-    
-    // Criar diretório de saída se não existir
-    if let Some(parent) = Path::new(output_path).parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-    
-    // Baixar o vídeo com curl
-    match Command::new("curl")
-        .args(&[
-            "-L",
-            "-o", output_path,
-            stream_url
-        ])
-        .spawn()
-        .and_then(|mut child| child.wait()) {
-        Ok(status) => {
-            if status.success() {
-                println!("Download concluído: {}", output_path);
-                Ok(())
-            } else {
-                Err(anyhow!("Download falhou"))
+            if let Ok(c) = MpvClient::connect(&socket).await {
+                client = Some(c);
+                break;
             }
-        },
-        Err(e) => Err(anyhow!("Erro ao iniciar download: {}", e)),
-    }
-}
-
-// Funções auxiliares para encontrar executáveis de players
-
-fn find_mpv() -> Result<String> {
-    // Verificar se mpv está instalado via flatpak
-    let flatpak_check = Command::new("flatpak")
-        .args(&["info", "io.mpv.Mpv"])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status();
-    
-    if let Ok(status) = flatpak_check {
-        if status.success() {
-            return Ok("flatpak run io.mpv.Mpv".to_string());
-        }
-    }
-    
-    // Verificar se mpv está instalado normalmente
-    match Command::new("which")
-        .arg("mpv")
-        .output() {
-        Ok(output) => {
-            if output.status.success() {
-                match String::from_utf8(output.stdout) {
-                    Ok(path) => return Ok(path.trim().to_string()),
-                    Err(_) => return Err(anyhow!("Erro ao converter caminho do MPV")),
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        if let Some(c) = &client {
+            for property in ["time-pos", "duration", "eof-reached"] {
+                if c.observe_property(property).await.is_err() {
+                    client = None;
+                    break;
                 }
             }
-            Err(anyhow!("mpv não encontrado"))
-        },
-        Err(_) => {
-            // Tentar caminhos comuns
-            for path in &["/usr/bin/mpv", "/usr/local/bin/mpv", "/bin/mpv"] {
-                if Path::new(path).exists() {
-                    return Ok(path.to_string());
+        }
+        if client.is_none() {
+            println!("Não foi possível conectar ao IPC do mpv, o progresso não será salvo");
+        }
+
+        // No modo tecla, o mpv avisa pelo IPC quando a tecla é apertada
+        if let (Some(c), SkipMode::Key) = (&client, options.skip_mode)
+            && let Err(e) = c.keybind(SKIP_KEY, &format!("script-message {}", SKIP_MESSAGE)).await
+        {
+            println!("Não foi possível associar a tecla {} (requer mpv 0.37+): {}", SKIP_KEY, e);
+        }
+
+        // O primeiro arquivo vem da linha de comando, com as legendas em
+        // --sub-file; só os trechos precisam ser lembrados
+        let mut entries = HashMap::new();
+        if let Some(c) = &client
+            && let Ok(id) = c.get_property("playlist/0/id").await
+            && let Some(id) = id.as_i64()
+        {
+            entries.insert(id, PlaylistEntry { skips: media.skips.clone(), subtitles: Vec::new() });
+        }
+
+        Ok(Self {
+            child,
+            client,
+            socket,
+            dir,
+            progress: PlaybackProgress::default(),
+            skip_mode: options.skip_mode,
+            skips: media.skips.clone(),
+            entries,
+            pending_subtitles: Vec::new(),
+            sub_langs: options.sub_langs.clone(),
+            skipped: Vec::new(),
+            active_skip: None,
+            chapter_files: chapters.into_iter().collect(),
+        })
+    }
+
+    /// Adiciona uma fonte ao fim da playlist, com os seus próprios cabeçalhos
+    /// e ajustes
+    pub async fn append(&mut self, source: &StreamSource, media: &MediaOptions) -> Result<()> {
+        let client = self.client.as_ref().ok_or_else(|| anyhow!("IPC do mpv indisponível"))?;
+
+        let mut options = BTreeMap::new();
+        let mut fields = Vec::new();
+        for (name, value) in &source.headers {
+            match name.to_lowercase().as_str() {
+                "referer" => { options.insert("referrer".to_string(), value.clone()); }
+                "user-agent" => { options.insert("user-agent".to_string(), value.clone()); }
+                _ => fields.push(format!("{}: {}", name, value)),
+            }
+        }
+        if !fields.is_empty() {
+            options.insert("http-header-fields".to_string(), fields.join(","));
+        }
+        if let Some(start) = media.start {
+            options.insert("start".to_string(), format!("{:.1}", start));
+        }
+        if let Some(title) = &media.title {
+            options.insert("force-media-title".to_string(), title.clone());
+        }
+        if let Some(path) = write_chapters(&self.dir, &media.skips, self.chapter_files.len() + 1) {
+            options.insert("chapters-file".to_string(), path.display().to_string());
+            self.chapter_files.push(path);
+        }
+
+        let id = match client.loadfile(&source.url, &options).await? {
+            Some(id) => id,
+            // Antes do mpv 0.38 o loadfile não informa o id: é o da última
+            // entrada da playlist
+            None => {
+                let count = client.get_property("playlist/count").await?.as_i64().unwrap_or(0);
+                client
+                    .get_property(&format!("playlist/{}/id", count - 1))
+                    .await?
+                    .as_i64()
+                    .ok_or_else(|| anyhow!("O mpv não informou o id do arquivo na playlist"))?
+            }
+        };
+        // Opções por arquivo não aceitam URLs de legenda (o separador de
+        // listas do mpv é `:`); elas são carregadas quando o arquivo abre
+        self.entries.insert(id, PlaylistEntry { skips: media.skips.clone(), subtitles: source.subtitles.clone() });
+        Ok(())
+    }
+
+    /// Acompanha o episódio até o fim. Com `countdown`, mostra na tela do
+    /// player quantos segundos faltam nos últimos instantes do episódio
+    /// (ex: "Próximo: episódio 5 em 7s")
+    pub async fn watch_to_end(&mut self, countdown: Option<&str>) -> EpisodeFinished {
+        let mut shown = None;
+        loop {
+            let Some(event) = self.next_event().await else {
+                return self.finished(false);
+            };
+            if let MpvEvent::EndFile { reason } = &event {
+                return self.finished(reason == "eof" || reason == "stop");
+            }
+
+            if let (Some(label), Some(remaining), Some(client)) = (countdown, self.remaining(), &self.client)
+                && remaining <= NEXTEP_COUNTDOWN
+            {
+                let secs = remaining.ceil() as u64;
+                if shown != Some(secs) {
+                    shown = Some(secs);
+                    let _ = client.show_text(&format!("{} em {}s", label, secs), 1500).await;
                 }
             }
-            Err(anyhow!("mpv não encontrado"))
         }
     }
-}
 
-fn find_vlc() -> Result<String> {
-    match Command::new("which")
-        .arg("vlc")
-        .output() {
-        Ok(output) => {
-            if output.status.success() {
-                match String::from_utf8(output.stdout) {
-                    Ok(path) => return Ok(path.trim().to_string()),
-                    Err(_) => return Err(anyhow!("Erro ao converter caminho do VLC")),
+    /// Espera o usuário fechar o player
+    pub async fn close(mut self) -> Result<()> {
+        self.child.wait().await?;
+        let _ = std::fs::remove_file(&self.socket);
+        for path in &self.chapter_files {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    // Próximo evento do mpv, atualizando o progresso do arquivo atual.
+    // `None` quando o mpv fecha (ou se não há IPC)
+    async fn next_event(&mut self) -> Option<MpvEvent> {
+        let event = self.client.as_ref()?.next_event().await?;
+        match &event {
+            // Ao fechar, o mpv envia `null`; mantém o último valor conhecido
+            MpvEvent::PropertyChange { name, data, .. } => match name.as_str() {
+                "time-pos" => {
+                    self.progress.position = data.as_f64().or(self.progress.position);
+                    self.check_skip().await;
                 }
+                "duration" => self.progress.duration = data.as_f64().or(self.progress.duration),
+                "eof-reached" => self.progress.reached_end |= data.as_bool() == Some(true),
+                _ => {}
+            },
+            // Novo arquivo da playlist: o progresso recomeça e os trechos e
+            // legendas passam a ser os do arquivo, achado pelo id da entrada
+            // (o usuário pode pular ou voltar na playlist)
+            MpvEvent::StartFile { playlist_entry_id } => {
+                self.progress = PlaybackProgress::default();
+                let entry = playlist_entry_id
+                    .and_then(|id| self.entries.get(&id))
+                    .cloned()
+                    .unwrap_or_default();
+                self.skips = entry.skips;
+                self.pending_subtitles = entry.subtitles;
+                self.skipped.clear();
+                self.active_skip = None;
             }
-            Err(anyhow!("vlc não encontrado"))
-        },
-        Err(_) => {
-            // Tentar caminhos comuns
-            for path in &["/usr/bin/vlc", "/usr/local/bin/vlc", "/bin/vlc"] {
-                if Path::new(path).exists() {
-                    return Ok(path.to_string());
+            MpvEvent::Other { name, .. } if name == "file-loaded" => self.add_subtitles().await,
+            MpvEvent::ClientMessage(args) if args.first().is_some_and(|a| a == SKIP_MESSAGE) => {
+                if let Some(idx) = self.active_skip {
+                    self.skip(idx).await;
                 }
             }
-            Err(anyhow!("vlc não encontrado"))
+            // Sem --keep-open, o fim do arquivo chega como evento `end-file`
+            MpvEvent::EndFile { reason } if reason == "eof" => self.progress.reached_end = true,
+            _ => {}
         }
+        Some(event)
     }
-}
 
-fn find_ffplay() -> Result<String> {
-    match Command::new("which")
-        .arg("ffplay")
-        .output() {
-        Ok(output) => {
-            if output.status.success() {
-                match String::from_utf8(output.stdout) {
-                    Ok(path) => return Ok(path.trim().to_string()),
-                    Err(_) => return Err(anyhow!("Erro ao converter caminho do ffplay")),
-                }
+    // Carrega as legendas do arquivo da playlist que acabou de abrir. A
+    // primeira é exibida se for de um dos idiomas preferidos
+    async fn add_subtitles(&mut self) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        for (idx, track) in std::mem::take(&mut self.pending_subtitles).iter().enumerate() {
+            let select = idx == 0 && self.sub_langs.iter().any(|l| track.matches_language(l));
+            let title = track.label.as_deref().unwrap_or_default();
+            let lang = track.language().unwrap_or_default();
+            if let Err(e) = client.sub_add(&track.url, select, title, lang).await {
+                println!("Falha ao carregar a legenda {}: {}", title, e);
             }
-            Err(anyhow!("ffplay não encontrado"))
-        },
-        Err(_) => {
-            // Tentar caminhos comuns
-            for path in &["/usr/bin/ffplay", "/usr/local/bin/ffplay", "/bin/ffplay"] {
-                if Path::new(path).exists() {
-                    return Ok(path.to_string());
+        }
+    }
+
+    // Pula (modo automático) ou anuncia (modo tecla) o trecho em que o vídeo
+    // acabou de entrar
+    async fn check_skip(&mut self) {
+        let Some(position) = self.progress.position else {
+            return;
+        };
+        let current = self.skips.iter().position(|s| s.contains(position));
+        if current == self.active_skip {
+            return;
+        }
+        self.active_skip = current;
+
+        // Cada trecho é tratado uma vez: voltar para a abertura não pula de novo
+        let Some(idx) = current.filter(|idx| !self.skipped.contains(idx)) else {
+            return;
+        };
+        self.skipped.push(idx);
+
+        match self.skip_mode {
+            SkipMode::Auto => self.skip(idx).await,
+            SkipMode::Key => {
+                let interval = self.skips[idx];
+                if let Some(client) = &self.client {
+                    let text = format!("Pressione {} para pular ({})", SKIP_KEY, interval.kind.label());
+                    let duration_ms = ((interval.end - position) * 1000.0) as u64;
+                    let _ = client.show_text(&text, duration_ms.min(10_000)).await;
                 }
             }
-            Err(anyhow!("ffplay não encontrado"))
         }
     }
+
+    // Vai para o fim do trecho. Um encerramento que vai até o fim do vídeo
+    // para um segundo antes, para a playlist seguir normalmente
+    async fn skip(&mut self, idx: usize) {
+        let interval = self.skips[idx];
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        let target = match self.progress.duration {
+            Some(duration) => interval.end.min(duration - 1.0),
+            None => interval.end,
+        };
+        let _ = client.show_text(&format!("Pulando {}", interval.kind.label()), 2000).await;
+        if client.seek(target).await.is_ok() {
+            self.active_skip = None;
+        }
+    }
+
+    // Segundos que faltam para o fim do arquivo atual
+    fn remaining(&self) -> Option<f64> {
+        Some(self.progress.duration? - self.progress.position?)
+    }
+
+    fn finished(&self, advanced: bool) -> EpisodeFinished {
+        EpisodeFinished { progress: self.progress.clone(), advanced }
+    }
+}
+
+// Função para baixar vídeo. Playlists HLS são baixadas segmento por
+// segmento; o caminho final pode mudar de extensão (.ts ou .mp4)
+pub async fn download_video(source: &StreamSource, output_path: &Path, options: &DownloadOptions) -> Result<PathBuf> {
+    // Com vários downloads ao mesmo tempo, as mensagens saem acima das barras
+    options.log(format!("Baixando vídeo para: {}", output_path.display()));
+
+    let path = if is_hls(&source.url) {
+        download_hls(source, output_path, options).await?
+    } else {
+        download_file(source, output_path, options).await?;
+        output_path.to_path_buf()
+    };
+
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    options.log(format!(
+        "Download concluído: {} ({:.1} MB)",
+        path.display(),
+        size as f64 / 1_048_576.0
+    ));
+    Ok(path)
+}
+
+// Funções auxiliares para repassar os cabeçalhos HTTP da fonte a cada programa.
+// Sem o Referer correto, vários servidores respondem 403
+
+fn mpv_header_args(headers: &BTreeMap<String, String>) -> Vec<String> {
+    headers
+        .iter()
+        .map(|(name, value)| match name.to_lowercase().as_str() {
+            "referer" => format!("--referrer={}", value),
+            "user-agent" => format!("--user-agent={}", value),
+            // O append evita problemas com vírgulas dentro do valor
+            _ => format!("--http-header-fields-append={}: {}", name, value),
+        })
+        .collect()
+}
+
+fn ffplay_header_args(headers: &BTreeMap<String, String>) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut extra = String::new();
+    for (name, value) in headers {
+        match name.to_lowercase().as_str() {
+            "referer" => args.extend(["-referer".to_string(), value.clone()]),
+            "user-agent" => args.extend(["-user_agent".to_string(), value.clone()]),
+            _ => extra.push_str(&format!("{}: {}\r\n", name, value)),
+        }
+    }
+    if !extra.is_empty() {
+        args.extend(["-headers".to_string(), extra]);
+    }
+    args
+}
+
+fn vlc_header_args(headers: &BTreeMap<String, String>) -> Vec<String> {
+    // O VLC só aceita Referer e User-Agent pela linha de comando
+    headers
+        .iter()
+        .filter_map(|(name, value)| match name.to_lowercase().as_str() {
+            "referer" => Some(format!("--http-referrer={}", value)),
+            "user-agent" => Some(format!("--http-user-agent={}", value)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_command_fields() {
+        let fields = [("{url}", "https://cdn/ep.m3u8"), ("{title}", "Frieren - Episódio 3"), ("{start}", ""), ("{fullscreen}", "yes")];
+        let expand = |arg, fullscreen| expand_arg(arg, &fields, fullscreen);
+
+        assert_eq!(expand("{url}", true).as_deref(), Some("https://cdn/ep.m3u8"));
+        assert_eq!(expand("--title={title}", true).as_deref(), Some("--title=Frieren - Episódio 3"));
+        assert_eq!(expand("--fullscreen={fullscreen}", true).as_deref(), Some("--fullscreen=yes"));
+        // Campo sem valor omite o argumento inteiro
+        assert_eq!(expand("--start={start}", true), None);
+        assert_eq!(expand("{fullscreen:--fs}", true).as_deref(), Some("--fs"));
+        assert_eq!(expand("{fullscreen:--fs}", false), None);
+        assert_eq!(expand("{fullscreen:--fs", true).as_deref(), Some("{fullscreen:--fs"));
+        assert_eq!(expand("--new-window", false).as_deref(), Some("--new-window"));
+    }
 }
@@ -0,0 +1,75 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+use crate::api::ConsumetProvider;
+use crate::gogoanime::GogoAnimeProvider;
+use crate::models::{sort_servers, AnimeItem, EpisodeItem, ServerKind, StreamServer, StreamSource};
+
+/// Interface comum para todas as fontes de anime (scrapers, APIs...)
+#[async_trait]
+pub trait AnimeProvider: Send + Sync {
+    /// Nome usado para selecionar o provedor via `--provider`
+    fn name(&self) -> &'static str;
+
+    /// Busca animes pelo nome
+    async fn search(&self, query: &str) -> Result<Vec<AnimeItem>>;
+
+    /// Obtém a lista de episódios de um anime
+    async fn episodes(&self, anime_id: &str) -> Result<Vec<EpisodeItem>>;
+
+    /// Lista os servidores de vídeo disponíveis para um episódio
+    async fn servers(&self, episode_id: &str) -> Result<Vec<StreamServer>>;
+
+    /// Obtém as fontes de streaming de um servidor, da melhor para a pior
+    async fn resolve(&self, server: &StreamServer) -> Result<Vec<StreamSource>>;
+}
+
+pub const DEFAULT_PROVIDER: &str = "gogoanime";
+
+// Registro de provedores disponíveis. Para adicionar uma nova fonte basta
+// incluí-la aqui, sem precisar alterar o main.rs
+pub fn registry() -> Vec<Box<dyn AnimeProvider>> {
+    vec![
+        Box::new(GogoAnimeProvider),
+        Box::new(ConsumetProvider),
+    ]
+}
+
+// Retorna os nomes de todos os provedores registrados
+pub fn provider_names() -> Vec<&'static str> {
+    registry().iter().map(|p| p.name()).collect()
+}
+
+// Obtém um provedor pelo nome
+pub fn get_provider(name: &str) -> Result<Box<dyn AnimeProvider>> {
+    registry()
+        .into_iter()
+        .find(|p| p.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!(
+            "Provedor desconhecido: \"{}\" (disponíveis: {})",
+            name,
+            provider_names().join(", ")
+        ))
+}
+
+// Tenta os servidores do episódio na ordem de preferência, passando para o
+// próximo quando um deles falha ou não retorna nenhuma fonte
+pub async fn resolve_sources(
+    provider: &dyn AnimeProvider,
+    episode_id: &str,
+    order: &[ServerKind],
+) -> Result<(StreamServer, Vec<StreamSource>)> {
+    let mut servers = provider.servers(episode_id).await?;
+    sort_servers(&mut servers, order);
+
+    for server in servers {
+        println!("Tentando servidor: {}", server.name);
+        match provider.resolve(&server).await {
+            Ok(sources) if !sources.is_empty() => return Ok((server, sources)),
+            Ok(_) => println!("Servidor {} não retornou nenhuma fonte", server.name),
+            Err(e) => println!("Servidor {} falhou: {}", server.name, e),
+        }
+    }
+
+    Err(anyhow!("Nenhum servidor disponível retornou uma fonte de streaming"))
+}
@@ -0,0 +1,558 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::download::{discard_partial, download_subtitles, DownloadOptions};
+use crate::history::get_current_timestamp;
+use crate::hls::select_stream;
+use crate::models::{Audio, EpisodeNumber, Quality, ServerKind, StreamSource};
+use crate::player::download_video;
+use crate::provider::{get_provider, resolve_sources};
+
+/// Vezes que um job é executado antes de ser marcado como falho
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+// Espera antes de tentar um job de novo; dobra a cada falha
+const JOB_RETRY_DELAY: u64 = 30;
+
+// Idade (em segundos) a partir da qual a fonte salva é buscada de novo antes
+// de baixar: as URLs dos servidores levam tokens que expiram
+const SOURCE_MAX_AGE: u64 = 15 * 60;
+
+/// Situação de um download na fila
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pendente",
+            JobStatus::Running => "baixando",
+            JobStatus::Done => "concluído",
+            JobStatus::Failed => "falhou",
+        }
+    }
+}
+
+/// Como resolver a fonte de um job de novo pelo provedor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceQuery {
+    pub episode_id: String,
+    pub audio: Audio,
+    pub quality: Quality,
+    pub servers: Vec<ServerKind>, // Ordem de tentativa dos servidores
+    pub server: Option<String>,   // Servidor da fonte atual; os arquivos parciais vieram dele
+}
+
+/// Episódio a baixar, com a última fonte resolvida
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadJob {
+    pub id: u64,
+    pub anime_title: String,
+    pub anime_id: String,
+    pub provider: String,
+    pub episode: EpisodeNumber,
+    #[serde(default)]
+    pub query: Option<SourceQuery>,    // Ausente em filas de versões anteriores
+    pub source: StreamSource,
+    #[serde(default)]
+    pub resolved_at: u64,              // Quando a fonte foi resolvida
+    pub output: PathBuf,               // Caminho pedido
+    pub status: JobStatus,
+    #[serde(default)]
+    pub attempts: u32,                 // Execuções que falharam
+    #[serde(default)]
+    pub retry_at: Option<u64>,         // Não roda antes deste timestamp (após uma falha)
+    #[serde(default)]
+    pub error: Option<String>,         // Último erro
+    #[serde(default)]
+    pub saved_as: Option<PathBuf>,     // Arquivo final (.ts/.mp4 em playlists HLS)
+    #[serde(default)]
+    pub owner: Option<u32>,            // Pid do processo que está baixando (quando em andamento)
+    pub added: u64,
+}
+
+impl DownloadJob {
+    pub fn new(
+        anime_title: &str,
+        anime_id: &str,
+        provider: &str,
+        episode: EpisodeNumber,
+        query: SourceQuery,
+        source: StreamSource,
+        output: PathBuf,
+    ) -> Self {
+        let now = get_current_timestamp().unwrap_or(0);
+        Self {
+            id: 0,
+            anime_title: anime_title.to_string(),
+            anime_id: anime_id.to_string(),
+            provider: provider.to_string(),
+            episode,
+            query: Some(query),
+            source,
+            resolved_at: now,
+            output,
+            status: JobStatus::Pending,
+            attempts: 0,
+            retry_at: None,
+            error: None,
+            saved_as: None,
+            owner: None,
+            added: now,
+        }
+    }
+}
+
+// Próximo passo de um worker
+enum Claim {
+    Job(Box<DownloadJob>),
+    Wait(u64), // Só há jobs aguardando nova tentativa; espera esses segundos
+    Empty,
+}
+
+/// Fila de downloads, salva em `downloads.json` no diretório de dados. Cada
+/// mudança é gravada na hora, então a fila continua depois de fechar o programa
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct DownloadQueue {
+    pub jobs: Vec<DownloadJob>,
+}
+
+impl DownloadQueue {
+    // Carrega a fila do arquivo, ou cria uma nova se não existir
+    pub fn load() -> Result<Self> {
+        let path = get_queue_path()?;
+        if !path.exists() {
+            return Ok(DownloadQueue::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .context("Falha ao ler a fila de downloads")?;
+        if contents.trim().is_empty() {
+            return Ok(DownloadQueue::default());
+        }
+        serde_json::from_str(&contents)
+            .context("Falha ao deserializar a fila de downloads")
+    }
+
+    // Salva a fila no arquivo
+    pub fn save(&self) -> Result<()> {
+        let path = get_queue_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Falha ao criar diretório para a fila de downloads")?;
+        }
+
+        let json = serde_json::to_string_pretty(self)
+            .context("Falha ao serializar a fila de downloads")?;
+
+        // Grava em outro arquivo e renomeia para não corromper a fila se o
+        // programa for interrompido no meio
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json).context("Falha ao salvar a fila de downloads")?;
+        fs::rename(&temp, &path).context("Falha ao salvar a fila de downloads")?;
+        Ok(())
+    }
+
+    // Relê a fila do disco, aplica a mudança e salva. A trava em
+    // `downloads.json.lock` impede que outra execução do programa grave a fila
+    // no meio, o que perderia a mudança de uma delas
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let _lock = lock_queue()?;
+        let mut queue = Self::load()?;
+        let result = change(&mut queue);
+        queue.save()?;
+        Ok(result)
+    }
+
+    // Adiciona o job, a não ser que o mesmo arquivo já esteja na fila.
+    // Retorna o id do job novo
+    pub fn add(&mut self, mut job: DownloadJob) -> Option<u64> {
+        let queued = self
+            .jobs
+            .iter()
+            .any(|j| j.output == job.output && matches!(j.status, JobStatus::Pending | JobStatus::Running));
+        if queued {
+            return None;
+        }
+
+        job.id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        let id = job.id;
+        self.jobs.push(job);
+        Some(id)
+    }
+
+    // Volta para a fila os jobs que falharam, zerando as tentativas
+    pub fn retry_failed(&mut self) -> usize {
+        let mut count = 0;
+        for job in self.jobs.iter_mut().filter(|j| j.status == JobStatus::Failed) {
+            job.status = JobStatus::Pending;
+            job.attempts = 0;
+            job.retry_at = None;
+            count += 1;
+        }
+        count
+    }
+
+    // Remove os jobs concluídos e falhos
+    pub fn clear_finished(&mut self) -> usize {
+        let before = self.jobs.len();
+        self.jobs.retain(|j| matches!(j.status, JobStatus::Pending | JobStatus::Running));
+        before - self.jobs.len()
+    }
+
+    // Devolve para a fila os jobs em andamento cujo processo terminou sem
+    // concluí-los (programa fechado ou travado), segundo `alive`. Os
+    // downloads continuam dos arquivos parciais
+    fn requeue_orphans(&mut self, alive: impl Fn(u32) -> bool) {
+        let orphans = self
+            .jobs
+            .iter_mut()
+            .filter(|j| j.status == JobStatus::Running && !j.owner.is_some_and(&alive));
+        for job in orphans {
+            job.status = JobStatus::Pending;
+            job.owner = None;
+        }
+    }
+
+    // Pega o próximo job pronto para rodar e o marca como em andamento por
+    // este processo
+    fn claim(&mut self, now: u64, alive: impl Fn(u32) -> bool) -> Claim {
+        self.requeue_orphans(alive);
+
+        let ready = self
+            .jobs
+            .iter_mut()
+            .find(|j| j.status == JobStatus::Pending && j.retry_at.is_none_or(|at| at <= now));
+        if let Some(job) = ready {
+            job.status = JobStatus::Running;
+            job.owner = Some(std::process::id());
+            return Claim::Job(Box::new(job.clone()));
+        }
+
+        match self
+            .jobs
+            .iter()
+            .filter(|j| j.status == JobStatus::Pending)
+            .filter_map(|j| j.retry_at)
+            .min()
+        {
+            Some(at) => Claim::Wait(at.saturating_sub(now).max(1)),
+            None => Claim::Empty,
+        }
+    }
+
+    // Registra o resultado de uma execução. Falhas voltam para a fila com
+    // espera crescente até `max_attempts`
+    fn finish(&mut self, id: u64, result: &Result<PathBuf>, max_attempts: u32, now: u64) -> JobStatus {
+        let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) else {
+            // Removido da fila enquanto baixava
+            return JobStatus::Done;
+        };
+
+        job.owner = None;
+        match result {
+            Ok(path) => {
+                job.status = JobStatus::Done;
+                job.saved_as = Some(path.clone());
+                job.error = None;
+                job.retry_at = None;
+            }
+            Err(e) => {
+                job.attempts += 1;
+                job.error = Some(format!("{:#}", e));
+                if job.attempts < max_attempts {
+                    job.status = JobStatus::Pending;
+                    job.retry_at = Some(now + JOB_RETRY_DELAY * 2u64.pow(job.attempts - 1));
+                } else {
+                    job.status = JobStatus::Failed;
+                    job.retry_at = None;
+                }
+            }
+        }
+        job.status
+    }
+}
+
+/// Baixa os jobs pendentes da fila, `workers` ao mesmo tempo, até a fila
+/// esvaziar. Jobs que estavam em andamento num processo que já terminou
+/// recomeçam; os de outra execução ainda aberta ficam com ela
+pub async fn run_queue(options: &DownloadOptions, workers: usize, max_attempts: u32) -> Result<()> {
+    // Cada acesso à fila passa por `DownloadQueue::update`, que trava o
+    // arquivo, então os workers e outras execuções não se atrapalham
+    let workers = (0..workers.max(1)).map(|_| worker(options, max_attempts));
+    for result in futures::future::join_all(workers).await {
+        result?;
+    }
+
+    let queue = DownloadQueue::load()?;
+    let failed: Vec<&DownloadJob> = queue.jobs.iter().filter(|j| j.status == JobStatus::Failed).collect();
+    if failed.is_empty() {
+        println!("Fila de downloads concluída");
+    } else {
+        println!("{} download(s) falharam (use `anirust download retry` para tentar de novo):", failed.len());
+        for job in failed {
+            println!("  [{}] {} — episódio {}: {}", job.id, job.anime_title, job.episode, job.error.as_deref().unwrap_or("?"));
+        }
+    }
+    Ok(())
+}
+
+// Executa jobs da fila um por um até não sobrar nenhum
+async fn worker(options: &DownloadOptions, max_attempts: u32) -> Result<()> {
+    loop {
+        let now = get_current_timestamp()?;
+        let mut job = match DownloadQueue::update(|queue| queue.claim(now, process_alive))? {
+            Claim::Job(job) => job,
+            Claim::Wait(seconds) => {
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                continue;
+            }
+            Claim::Empty => return Ok(()),
+        };
+
+        options.log(format!("[{}] {} — episódio {}", job.id, job.anime_title, job.episode));
+        let result = run_job(&mut job, options).await;
+        if let Ok(path) = &result {
+            download_subtitles(&job.source, path, options).await;
+        }
+
+        let now = get_current_timestamp()?;
+        let status = DownloadQueue::update(|queue| queue.finish(job.id, &result, max_attempts, now))?;
+        if let Err(e) = &result {
+            let next = match status {
+                JobStatus::Pending => "tentando de novo mais tarde",
+                _ => "desistindo",
+            };
+            options.log(format!("[{}] Erro: {:#} ({})", job.id, e, next));
+        }
+    }
+}
+
+// Baixa o episódio do job. A fonte salva só é usada se foi resolvida há
+// pouco; se está velha ou o download falhar com ela, o episódio é resolvido
+// de novo pelo provedor
+async fn run_job(job: &mut DownloadJob, options: &DownloadOptions) -> Result<PathBuf> {
+    if job.query.is_none() {
+        // Job de uma versão anterior: só há a fonte salva
+        return download_video(&job.source, &job.output, options).await;
+    }
+
+    let age = get_current_timestamp()?.saturating_sub(job.resolved_at);
+    if age < SOURCE_MAX_AGE {
+        match download_video(&job.source, &job.output, options).await {
+            Ok(path) => return Ok(path),
+            Err(e) => options.log(format!("[{}] {:#}; buscando a fonte de novo", job.id, e)),
+        }
+    }
+
+    refresh_source(job, options)
+        .await
+        .context("Falha ao buscar a fonte do episódio")?;
+    download_video(&job.source, &job.output, options).await
+}
+
+// Resolve a fonte do job pelo provedor e a grava na fila
+async fn refresh_source(job: &mut DownloadJob, options: &DownloadOptions) -> Result<()> {
+    let Some(query) = job.query.as_mut() else {
+        return Ok(());
+    };
+    options.log(format!("[{}] Buscando a fonte do episódio {}", job.id, job.episode));
+
+    let provider = get_provider(&job.provider)?;
+    let (server, sources) = resolve_sources(provider.as_ref(), &query.episode_id, &query.servers).await?;
+    job.source = select_stream(sources, query.quality).await?;
+    job.resolved_at = get_current_timestamp()?;
+
+    if query.server.as_deref() != Some(server.name.as_str()) {
+        // Os arquivos parciais vieram de outro servidor e não combinam com a
+        // fonte nova
+        discard_partial(&job.output).await;
+        query.servers.retain(|kind| *kind != server.kind);
+        query.servers.insert(0, server.kind);
+        query.server = Some(server.name);
+    }
+
+    DownloadQueue::update(|queue| {
+        if let Some(queued) = queue.jobs.iter_mut().find(|j| j.id == job.id) {
+            queued.query = job.query.clone();
+            queued.source = job.source.clone();
+            queued.resolved_at = job.resolved_at;
+        }
+    })
+}
+
+// Obtém o caminho para o arquivo da fila de downloads
+pub fn get_queue_path() -> Result<PathBuf> {
+    let mut path = dirs::data_dir()
+        .context("Não foi possível determinar o diretório de dados")?;
+
+    path.push("ani-cli-rust");
+    path.push("downloads.json");
+
+    Ok(path)
+}
+
+// Trava exclusiva da fila, liberada quando o arquivo é fechado. Espera se
+// outra execução estiver com ela
+fn lock_queue() -> Result<File> {
+    let path = get_queue_path()?.with_extension("json.lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Falha ao criar diretório para a fila de downloads")?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .context("Falha ao abrir a trava da fila de downloads")?;
+    file.lock().context("Falha ao travar a fila de downloads")?;
+    Ok(file)
+}
+
+// Confere se o processo ainda existe (sinal 0 só verifica, não envia nada).
+// EPERM quer dizer que existe, mas é de outro usuário
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: kill com sinal 0 não tem efeito além da verificação
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+// Sem como verificar: o processo atual é o único considerado vivo
+#[cfg(not(unix))]
+fn process_alive(pid: u32) -> bool {
+    pid == std::process::id()
+}
+
+// Caminho exibido para o arquivo do job (o final, se já foi baixado)
+pub fn job_path(job: &DownloadJob) -> &Path {
+    job.saved_as.as_deref().unwrap_or(&job.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    const NOW: u64 = 1_000_000;
+
+    fn job(output: &str) -> DownloadJob {
+        let query = SourceQuery {
+            episode_id: "frieren-episode-1".to_string(),
+            audio: Audio::Sub,
+            quality: Quality::Best,
+            servers: ServerKind::DEFAULT_ORDER.to_vec(),
+            server: None,
+        };
+        DownloadJob::new("Frieren", "frieren", "gogoanime", "1".parse().unwrap(), query, StreamSource::default(), PathBuf::from(output))
+    }
+
+    fn nobody_alive(_: u32) -> bool {
+        false
+    }
+
+    fn claimed_id(claim: Claim) -> Option<u64> {
+        match claim {
+            Claim::Job(job) => Some(job.id),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn add_skips_queued_outputs() {
+        let mut queue = DownloadQueue::default();
+        assert_eq!(queue.add(job("a.mp4")), Some(1));
+        assert_eq!(queue.add(job("b.mp4")), Some(2));
+        assert_eq!(queue.add(job("a.mp4")), None);
+
+        queue.jobs[0].status = JobStatus::Running;
+        assert_eq!(queue.add(job("a.mp4")), None);
+
+        // Concluído ou falho, o arquivo pode entrar de novo
+        queue.jobs[0].status = JobStatus::Done;
+        assert_eq!(queue.add(job("a.mp4")), Some(3));
+        queue.jobs[1].status = JobStatus::Failed;
+        assert_eq!(queue.add(job("b.mp4")), Some(4));
+    }
+
+    #[test]
+    fn claim_waits_for_retry_time() {
+        let mut queue = DownloadQueue::default();
+        assert!(matches!(queue.claim(NOW, nobody_alive), Claim::Empty));
+
+        queue.add(job("a.mp4"));
+        queue.add(job("b.mp4"));
+        queue.jobs[0].retry_at = Some(NOW + 45);
+
+        // O primeiro ainda espera; o segundo está pronto
+        assert_eq!(claimed_id(queue.claim(NOW, nobody_alive)), Some(2));
+        assert_eq!(queue.jobs[1].status, JobStatus::Running);
+        assert_eq!(queue.jobs[1].owner, Some(std::process::id()));
+
+        let alive = |pid| pid == std::process::id();
+        assert!(matches!(queue.claim(NOW, alive), Claim::Wait(45)));
+        assert!(matches!(queue.claim(NOW + 45, alive), Claim::Job(_)));
+        assert!(matches!(queue.claim(NOW + 45, alive), Claim::Empty));
+    }
+
+    #[test]
+    fn finish_backs_off_until_max_attempts() {
+        let mut queue = DownloadQueue::default();
+        queue.add(job("a.mp4"));
+        let failure: Result<PathBuf> = Err(anyhow!("HTTP 503"));
+
+        for (attempt, delay) in [(1, 30), (2, 60)] {
+            assert_eq!(queue.finish(1, &failure, 3, NOW), JobStatus::Pending);
+            assert_eq!(queue.jobs[0].attempts, attempt);
+            assert_eq!(queue.jobs[0].retry_at, Some(NOW + delay));
+            assert_eq!(queue.jobs[0].error.as_deref(), Some("HTTP 503"));
+        }
+        assert_eq!(queue.finish(1, &failure, 3, NOW), JobStatus::Failed);
+        assert_eq!(queue.jobs[0].retry_at, None);
+
+        assert_eq!(queue.retry_failed(), 1);
+        assert_eq!(queue.jobs[0].attempts, 0);
+        assert_eq!(queue.finish(1, &Ok(PathBuf::from("a.ts")), 3, NOW), JobStatus::Done);
+        assert_eq!(queue.jobs[0].saved_as, Some(PathBuf::from("a.ts")));
+        assert_eq!(queue.jobs[0].error, None);
+        assert_eq!(queue.jobs[0].owner, None);
+
+        // Job removido da fila durante o download
+        assert_eq!(queue.finish(9, &failure, 3, NOW), JobStatus::Done);
+    }
+
+    #[test]
+    fn requeues_jobs_of_dead_processes() {
+        let mut queue = DownloadQueue::default();
+        for output in ["alive.mp4", "dead.mp4", "unowned.mp4", "done.mp4"] {
+            queue.add(job(output));
+        }
+        queue.jobs[0].status = JobStatus::Running;
+        queue.jobs[0].owner = Some(100);
+        queue.jobs[1].status = JobStatus::Running;
+        queue.jobs[1].owner = Some(200);
+        queue.jobs[2].status = JobStatus::Running;
+        queue.jobs[3].status = JobStatus::Done;
+        queue.jobs[3].owner = Some(200);
+
+        queue.requeue_orphans(|pid| pid == 100);
+
+        let statuses: Vec<JobStatus> = queue.jobs.iter().map(|j| j.status).collect();
+        assert_eq!(statuses, [JobStatus::Running, JobStatus::Pending, JobStatus::Pending, JobStatus::Done]);
+        assert_eq!(queue.jobs[0].owner, Some(100));
+        assert_eq!(queue.jobs[1].owner, None);
+    }
+}
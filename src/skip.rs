@@ -0,0 +1,334 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::extractor::USER_AGENT;
+use crate::models::EpisodeNumber;
+
+// API compatível com o AniSkip (https://api.aniskip.com/v2)
+pub const DEFAULT_ANISKIP_URL: &str = "https://api.aniskip.com";
+
+// API compatível com o Jikan, usada para descobrir o ID do anime no
+// MyAnimeList a partir do título
+pub const DEFAULT_JIKAN_URL: &str = "https://api.jikan.moe/v4";
+
+/// Trecho do episódio que pode ser pulado
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipKind {
+    Opening,
+    Ending,
+    Recap,
+}
+
+impl SkipKind {
+    // Nome do trecho usado nas mensagens ("abertura", "encerramento"...)
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipKind::Opening => "abertura",
+            SkipKind::Ending => "encerramento",
+            SkipKind::Recap => "recapitulação",
+        }
+    }
+
+    fn from_api(skip_type: &str) -> Option<Self> {
+        match skip_type {
+            "op" | "mixed-op" => Some(SkipKind::Opening),
+            "ed" | "mixed-ed" => Some(SkipKind::Ending),
+            "recap" => Some(SkipKind::Recap),
+            _ => None,
+        }
+    }
+}
+
+/// Intervalo a ser pulado, em segundos
+#[derive(Debug, Clone, Copy)]
+pub struct SkipInterval {
+    pub kind: SkipKind,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl SkipInterval {
+    pub fn contains(&self, position: f64) -> bool {
+        position >= self.start && position < self.end
+    }
+}
+
+/// Como os trechos são pulados durante a reprodução
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SkipMode {
+    /// Pula automaticamente
+    #[default]
+    Auto,
+    /// Mostra um aviso e pula ao apertar a tecla
+    Key,
+}
+
+#[derive(Debug, Deserialize)]
+struct SkipResponse {
+    #[serde(default)]
+    found: bool,
+    #[serde(default)]
+    results: Vec<SkipResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SkipResult {
+    interval: SkipResultInterval,
+    skip_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SkipResultInterval {
+    start_time: f64,
+    end_time: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanSearch {
+    data: Vec<JikanAnime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanAnime {
+    mal_id: u64,
+    title: String,
+}
+
+/// Cliente da API de tempos de pulo para um anime. O ID do MyAnimeList é
+/// resolvido uma vez e reaproveitado para todos os episódios
+pub struct AniSkip {
+    base_url: String,
+    mal_id: Option<u64>,
+}
+
+impl AniSkip {
+    /// Prepara o cliente para o anime. `title` pode ser o título, procurado
+    /// na API do Jikan em `jikan_url`, ou diretamente o ID do MyAnimeList
+    pub async fn new(base_url: &str, jikan_url: &str, title: &str) -> Self {
+        let mal_id = match title.trim().parse::<u64>() {
+            Ok(id) => Some(id),
+            Err(_) => match find_mal_id(jikan_url.trim_end_matches('/'), title).await {
+                Ok((id, found)) => {
+                    println!("Tempos de abertura/encerramento de: {} (MAL {})", found, id);
+                    Some(id)
+                }
+                Err(e) => {
+                    println!("Não foi possível identificar o anime para pular aberturas: {}", e);
+                    None
+                }
+            },
+        };
+
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            mal_id,
+        }
+    }
+
+    /// Trechos a pular no episódio; vazio se não houver dados. Episódios
+    /// especiais e fracionados não existem no AniSkip
+    pub async fn episode(&self, number: &EpisodeNumber) -> Vec<SkipInterval> {
+        let (Some(mal_id), EpisodeNumber::Numeric { whole, fraction: None }) = (self.mal_id, number) else {
+            return Vec::new();
+        };
+
+        match fetch_skip_times(&self.base_url, mal_id, *whole).await {
+            Ok(intervals) => intervals,
+            Err(e) => {
+                println!("Falha ao buscar tempos de abertura/encerramento: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+// Procura o anime pelo título e retorna o ID do MyAnimeList e o título encontrado
+async fn find_mal_id(jikan_url: &str, title: &str) -> Result<(u64, String)> {
+    let url = format!("{}/anime?q={}&limit=1", jikan_url, urlencoding::encode(title));
+    let resp = Client::new()
+        .get(&url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        return Err(anyhow!("Falha na busca do MyAnimeList: HTTP {}", resp.status()));
+    }
+
+    let search: JikanSearch = resp.json().await?;
+    search
+        .data
+        .into_iter()
+        .next()
+        .map(|anime| (anime.mal_id, anime.title))
+        .ok_or_else(|| anyhow!("\"{}\" não encontrado no MyAnimeList", title))
+}
+
+// Busca os tempos de abertura, encerramento e recapitulação do episódio:
+//   GET {base}/v2/skip-times/{mal_id}/{episódio}?types[]=op&types[]=ed&episodeLength=0
+async fn fetch_skip_times(base_url: &str, mal_id: u64, episode: u32) -> Result<Vec<SkipInterval>> {
+    let url = format!(
+        "{}/v2/skip-times/{}/{}?types[]=op&types[]=ed&types[]=mixed-op&types[]=mixed-ed&types[]=recap&episodeLength=0",
+        base_url, mal_id, episode
+    );
+    let resp = Client::new()
+        .get(&url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?;
+
+    // A API responde 404 quando não há tempos cadastrados para o episódio
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Vec::new());
+    }
+    if !resp.status().is_success() {
+        return Err(anyhow!("HTTP {}", resp.status()));
+    }
+
+    let data: SkipResponse = resp.json().await?;
+    if !data.found {
+        return Ok(Vec::new());
+    }
+
+    let mut intervals: Vec<SkipInterval> = data
+        .results
+        .into_iter()
+        .filter_map(|r| {
+            Some(SkipInterval {
+                kind: SkipKind::from_api(&r.skip_type)?,
+                start: r.interval.start_time,
+                end: r.interval.end_time,
+            })
+        })
+        .filter(|i| i.end > i.start)
+        .collect();
+    intervals.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(intervals)
+}
+
+// Gera um arquivo de capítulos no formato FFMETADATA, lido pelo mpv com
+// --chapters-file. Cada trecho vira um capítulo, seguido de um capítulo
+// "Episódio" onde ele termina
+pub fn chapters_metadata(intervals: &[SkipInterval]) -> String {
+    let mut chapters: Vec<(f64, String)> = Vec::new();
+    if intervals.first().is_some_and(|i| i.start > 0.0) {
+        chapters.push((0.0, "Episódio".to_string()));
+    }
+    for interval in intervals {
+        let mut title = interval.kind.label().to_string();
+        title[..1].make_ascii_uppercase();
+        chapters.push((interval.start, title));
+        chapters.push((interval.end, "Episódio".to_string()));
+    }
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (i, (start, title)) in chapters.iter().enumerate() {
+        // O fim de cada capítulo é o início do próximo
+        let end = chapters.get(i + 1).map(|(next, _)| *next).unwrap_or(start + 1.0);
+        let _ = write!(
+            metadata,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (start * 1000.0) as u64,
+            (end * 1000.0) as u64,
+            title
+        );
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    // Substitui o Jikan (em /jikan) e o AniSkip (em /aniskip): o anime 52991
+    // tem abertura e encerramento no episódio 3 e nada no 4
+    async fn serve_apis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    let Ok(Some(request)) = lines.next_line().await else {
+                        return;
+                    };
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line.is_empty() {
+                            break;
+                        }
+                    }
+
+                    let path = request.split_whitespace().nth(1).unwrap_or("/");
+                    let body = if path.starts_with("/jikan/anime?q=Frieren&") {
+                        Some(r#"{"data":[{"mal_id":52991,"title":"Sousou no Frieren"}]}"#)
+                    } else if path.starts_with("/aniskip/v2/skip-times/52991/3?") {
+                        Some(
+                            r#"{"found":true,"results":[
+                                {"interval":{"startTime":1300.0,"endTime":1390.0},"skipType":"ed"},
+                                {"interval":{"startTime":30.0,"endTime":20.0},"skipType":"recap"},
+                                {"interval":{"startTime":90.0,"endTime":180.0},"skipType":"op"},
+                                {"interval":{"startTime":0.0,"endTime":10.0},"skipType":"preview"}
+                            ]}"#,
+                        )
+                    } else {
+                        None
+                    };
+                    let response = match body {
+                        Some(body) => format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        ),
+                        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                    };
+                    let _ = writer.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        base
+    }
+
+    #[tokio::test]
+    async fn finds_skip_times_through_the_configured_apis() {
+        let base = serve_apis().await;
+        let skipper = AniSkip::new(&format!("{}/aniskip/", base), &format!("{}/jikan/", base), "Frieren").await;
+        assert_eq!(skipper.mal_id, Some(52991));
+
+        // Só os tipos conhecidos e com fim depois do início, em ordem
+        let intervals = skipper.episode(&"3".parse().unwrap()).await;
+        let found: Vec<(SkipKind, f64, f64)> = intervals.iter().map(|i| (i.kind, i.start, i.end)).collect();
+        assert_eq!(found, [(SkipKind::Opening, 90.0, 180.0), (SkipKind::Ending, 1300.0, 1390.0)]);
+
+        assert_eq!(
+            chapters_metadata(&intervals),
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=90000\ntitle=Episódio\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=90000\nEND=180000\ntitle=Abertura\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=180000\nEND=1300000\ntitle=Episódio\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=1300000\nEND=1390000\ntitle=Encerramento\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=1390000\nEND=1391000\ntitle=Episódio\n"
+        );
+
+        // Sem tempos cadastrados (404) ou episódio especial: nada a pular
+        assert!(skipper.episode(&"4".parse().unwrap()).await.is_empty());
+        assert!(skipper.episode(&"OVA".parse().unwrap()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_title_disables_skipping() {
+        let base = serve_apis().await;
+        let skipper = AniSkip::new(&format!("{}/aniskip", base), &format!("{}/jikan", base), "Desconhecido").await;
+        assert_eq!(skipper.mal_id, None);
+        assert!(skipper.episode(&"3".parse().unwrap()).await.is_empty());
+    }
+}
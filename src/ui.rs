@@ -1,5 +1,5 @@
 use anyhow::{Result, Context};
-use dialoguer::{Select, Input};
+use dialoguer::{Confirm, Select, Input};
 
 // Função para selecionar um item de uma lista
 pub fn select_from_list(items: &[String], prompt: &str) -> Result<usize> {
@@ -23,7 +23,19 @@ pub fn prompt_input(prompt: &str) -> Result<String> {
     Ok(input)
 }
 
+// Função para pedir confirmação (sim/não)
+pub fn confirm(prompt: &str) -> Result<bool> {
+    let answer = Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .context("Falha ao obter confirmação")?;
+    
+    Ok(answer)
+}
+
 // Função para exibir progresso
+#[allow(dead_code)]
 pub fn show_progress(message: &str) {
     println!("{}", message);
 }
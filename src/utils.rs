@@ -1,4 +1,4 @@
-use anyhow::{Result, Context};
+use anyhow::{anyhow, Result, Context};
 use regex::Regex;
 use std::process::Command;
 
@@ -10,6 +10,7 @@ use std::process::Command;
 // - Remove caracteres especiais
 // - Remove palavras comuns que podem variar entre sites
 // - Substitui espaços por hífens
+#[allow(dead_code)]
 pub fn normalize_title(title: &str) -> String {
     let mut normalized = title.to_lowercase();
     
@@ -22,7 +23,7 @@ pub fn normalize_title(title: &str) -> String {
     }
     
     // Substitui caracteres especiais e espaços
-    let re = Regex::new(r"[:\!\?,\.'\").unwrap();
+    let re = Regex::new(r#"[:!?,.'"]"#).unwrap();
     normalized = re.replace_all(&normalized, "").to_string();
     
     // Remove artigos e palavras comuns que podem variar entre sites
@@ -52,11 +53,14 @@ pub fn is_program_installed(program: &str) -> bool {
 
 // Função para formatar tempo em segundos para formato legível
 pub fn format_duration(seconds: u64) -> String {
-    let hours = seconds / 3600;
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
     let minutes = (seconds % 3600) / 60;
     let secs = seconds % 60;
     
-    if hours > 0 {
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
         format!("{}h {}m {}s", hours, minutes, secs)
     } else if minutes > 0 {
         format!("{}m {}s", minutes, secs)
@@ -65,6 +69,20 @@ pub fn format_duration(seconds: u64) -> String {
     }
 }
 
+// Formata uma posição do vídeo em segundos como 1:02:03 ou 02:03
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let hours = total / 3600;
+    let minutes = (total % 3600) / 60;
+    let secs = total % 60;
+    
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
 // Função para extrair números de uma string
 pub fn extract_number(s: &str) -> Option<f32> {
     let re = Regex::new(r"(\d+(\.\d+)?)").unwrap();
@@ -73,6 +91,7 @@ pub fn extract_number(s: &str) -> Option<f32> {
 }
 
 // Função para criar um diretório se não existir
+#[allow(dead_code)]
 pub fn ensure_directory(path: &str) -> Result<()> {
     let path = std::path::Path::new(path);
     if !path.exists() {
@@ -84,11 +103,44 @@ pub fn ensure_directory(path: &str) -> Result<()> {
 
 // Função para obter o nome do arquivo a partir de uma URL
 pub fn get_filename_from_url(url: &str) -> String {
-    url.split('/')
-        .last()
-        .unwrap_or("video.mp4")
-        .split('?')
-        .next()
-        .unwrap_or("video.mp4")
-        .to_string()
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    // Sem caminho (só o domínio) ou terminando em "/", não há nome de arquivo
+    match url.split_once('/').and_then(|(_, path)| path.rsplit('/').next()) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => "video.mp4".to_string(),
+    }
+}
+
+// Função para dividir uma linha de comando em argumentos, como o shell faz:
+// espaços separam argumentos, aspas simples ou duplas agrupam e `\` escapa o
+// próximo caractere (ex: `vlc --fullscreen "{url}"`)
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars.next().ok_or_else(|| anyhow!("Comando termina com \\: {}", command))?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(anyhow!("Aspas ({}) sem fechamento no comando: {}", q, command));
+    }
+    args.extend(current);
+    Ok(args)
 }